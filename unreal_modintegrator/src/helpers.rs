@@ -8,7 +8,7 @@ use regex::Regex;
 use unreal_asset::Asset;
 use unreal_pak::PakFile;
 
-use crate::{find_asset, read_asset};
+use crate::{find_asset, is_asset_deleted, read_asset};
 
 lazy_static! {
     static ref GAME_REGEX: Regex = Regex::new(r"^/Game/").unwrap();
@@ -47,6 +47,12 @@ pub fn get_asset(
         return read_asset(&mut mod_paks[mod_asset], version, name)
             .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()));
     }
+    if is_asset_deleted(mod_paks, name) {
+        return Err(io::Error::new(
+            ErrorKind::Other,
+            format!("Asset {} was deleted by a mod", name),
+        ));
+    }
 
     let original_asset = find_asset(game_paks, name)
         .ok_or_else(|| io::Error::new(ErrorKind::Other, format!("No such asset {}", name)))?;
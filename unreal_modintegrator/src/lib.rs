@@ -80,13 +80,24 @@ pub trait IntegratorConfig<'data, T, E: std::error::Error> {
 
 pub fn find_asset(paks: &mut [PakFile], name: &String) -> Option<usize> {
     for (i, pak) in paks.iter().enumerate() {
-        if pak.records.contains_key(name) {
-            return Some(i);
+        if let Some(record) = pak.records.get(name) {
+            // a delete record removes the asset from all following paks
+            return match record.is_deleted() {
+                true => None,
+                false => Some(i),
+            };
         }
     }
     None
 }
 
+/// Whether the first pak containing the asset has a delete record for it
+pub fn is_asset_deleted(paks: &[PakFile], name: &String) -> bool {
+    paks.iter()
+        .find_map(|e| e.records.get(name))
+        .is_some_and(|e| e.is_deleted())
+}
+
 pub fn read_asset(pak: &mut PakFile, engine_version: i32, name: &String) -> Result<Asset, Error> {
     let uexp = pak
         .get_record(
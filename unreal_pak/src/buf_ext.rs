@@ -1,9 +1,11 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
+use crate::error::UnrealPakError;
+
 pub trait BufReaderExt {
-    fn read_string(&mut self) -> Result<Option<String>, std::io::Error>;
+    fn read_string(&mut self) -> Result<Option<String>, UnrealPakError>;
 }
 pub trait BufWriterExt {
     fn write_string(&mut self, string: Option<&str>) -> Result<(), std::io::Error>;
@@ -13,20 +15,38 @@ impl<R> BufReaderExt for R
 where
     R: Read + Seek,
 {
-    fn read_string(&mut self) -> Result<Option<String>, std::io::Error> {
-        // todo: unicode encoding
-        let len = self.read_u32::<LittleEndian>()?;
+    fn read_string(&mut self) -> Result<Option<String>, UnrealPakError> {
+        let len = self.read_i32::<LittleEndian>()?;
+
+        // negative lengths are utf-16 strings, both include a null terminator
+        let (is_unicode, size) = match len {
+            0 => return Ok(None),
+            1.. => (false, len as u64),
+            _ => (true, len.unsigned_abs() as u64 * 2),
+        };
 
-        if len == 0 {
-            return Ok(None);
+        // the length is not trusted for allocating, only the data that is actually there is read
+        let mut buf = Vec::new();
+        self.take(size).read_to_end(&mut buf)?;
+        if buf.len() as u64 != size {
+            let offset = self.stream_position()? - buf.len() as u64;
+            return Err(UnrealPakError::out_of_bounds(offset, size));
         }
 
-        let mut buf = vec![0u8; len as usize - 1];
-        self.read_exact(&mut buf)?;
-        self.seek(SeekFrom::Current(1))?;
-        Ok(Some(
-            String::from_utf8(buf).unwrap_or_else(|_| String::from("None")),
-        ))
+        let string = match is_unicode {
+            true => {
+                let chars: Vec<u16> = buf
+                    .chunks_exact(2)
+                    .map(|e| u16::from_le_bytes([e[0], e[1]]))
+                    .collect();
+                String::from_utf16_lossy(&chars[..chars.len() - 1])
+            }
+            false => {
+                buf.pop();
+                String::from_utf8(buf).unwrap_or_else(|_| String::from("None"))
+            }
+        };
+        Ok(Some(string))
     }
 }
 
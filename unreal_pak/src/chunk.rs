@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use crate::query::glob_matches;
+use crate::vfs::normalize_path;
+
+/// Extensions of the files of a single asset, which are always kept in the same chunk,
+/// `.m.ubulk` has to come before `.ubulk`
+const ASSET_EXTENSIONS: [&str; 7] = [
+    ".uasset", ".umap", ".uexp", ".m.ubulk", ".ubulk", ".uptnl", ".ufont",
+];
+
+/// Rule assigning the files matching a glob pattern to a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkRule {
+    /// Pattern like `Game/Content/Maps/**`, see `RecordIndex::glob`
+    pub pattern: String,
+    pub chunk_id: u32,
+}
+
+/// Splits files across `pakchunkN` paks by rules and a maximum pak size
+///
+/// Rules are checked in the order they were added, the first matching rule decides the
+/// chunk of a file and files without a matching rule go to `default_chunk`. Chunks larger
+/// than `max_size` are split into parts, named `pakchunkN_sM` like UnrealPak does.
+/// The `.uasset` or `.umap`, `.uexp`, `.ubulk`, `.m.ubulk`, `.uptnl` and `.ufont` files of an asset
+/// always end up in the same pak.
+#[derive(Debug, Clone, Default)]
+pub struct PakChunker {
+    /// Largest total file size of a pak, an asset larger than this gets a pak of its own
+    pub max_size: Option<u64>,
+    /// Chunk of files that don't match any rule
+    pub default_chunk: u32,
+    rules: Vec<ChunkRule>,
+}
+
+/// Files assigned to a single pak
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakChunk {
+    pub chunk_id: u32,
+    /// Index of the part when the chunk was split, 0 for the first pak of a chunk
+    pub part: u32,
+    /// Total size of the files
+    pub size: u64,
+    /// File paths, sorted case insensitive
+    pub files: Vec<String>,
+}
+
+/// Result of `PakChunker::assign`, chunks are sorted by chunk id and part
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkPlan {
+    pub chunks: Vec<PakChunk>,
+}
+
+/// Files of an asset, or a single file that isn't part of an asset
+struct FileGroup {
+    files: Vec<String>,
+    size: u64,
+}
+
+impl PakChunker {
+    pub fn new() -> Self {
+        PakChunker::default()
+    }
+
+    pub fn add_rule(&mut self, pattern: &str, chunk_id: u32) {
+        self.rules.push(ChunkRule {
+            pattern: pattern.to_string(),
+            chunk_id,
+        });
+    }
+
+    pub fn rules(&self) -> &[ChunkRule] {
+        &self.rules
+    }
+
+    /// Chunk a path is assigned to by the rules
+    pub fn chunk_id(&self, path: &str) -> u32 {
+        self.group_chunk_id(&[path.to_string()])
+    }
+
+    /// Chunk of the first rule that matches any of the files
+    fn group_chunk_id(&self, files: &[String]) -> u32 {
+        let files: Vec<String> = files.iter().map(|e| normalize_path(e)).collect();
+        self.rules
+            .iter()
+            .find(|rule| {
+                let pattern = normalize_path(&rule.pattern);
+                let pattern_segments: Vec<&str> = pattern.split('/').collect();
+                files.iter().any(|e| glob_matches(&pattern_segments, e))
+            })
+            .map_or(self.default_chunk, |rule| rule.chunk_id)
+    }
+
+    /// Split files given by path and size into paks
+    pub fn assign<'a>(&self, files: impl IntoIterator<Item = (&'a str, u64)>) -> ChunkPlan {
+        let mut groups: BTreeMap<String, FileGroup> = BTreeMap::new();
+        for (path, size) in files {
+            let group = groups.entry(asset_key(path)).or_insert(FileGroup {
+                files: Vec::new(),
+                size: 0,
+            });
+            group.files.push(path.to_string());
+            group.size += size;
+        }
+
+        // an asset goes to the chunk of the first rule that matches any of its files
+        let mut chunks: BTreeMap<u32, Vec<FileGroup>> = BTreeMap::new();
+        for mut group in groups.into_values() {
+            group.files.sort_by_key(|e| normalize_path(e));
+            let chunk_id = self.group_chunk_id(&group.files);
+            chunks.entry(chunk_id).or_default().push(group);
+        }
+
+        let mut plan = ChunkPlan::default();
+        for (chunk_id, groups) in chunks {
+            let mut current = PakChunk {
+                chunk_id,
+                part: 0,
+                size: 0,
+                files: Vec::new(),
+            };
+            for group in groups {
+                let full = self
+                    .max_size
+                    .is_some_and(|max_size| current.size + group.size > max_size);
+                if full && !current.files.is_empty() {
+                    let part = current.part + 1;
+                    plan.chunks.push(std::mem::replace(
+                        &mut current,
+                        PakChunk {
+                            chunk_id,
+                            part,
+                            size: 0,
+                            files: Vec::new(),
+                        },
+                    ));
+                }
+                current.size += group.size;
+                current.files.extend(group.files);
+            }
+            plan.chunks.push(current);
+        }
+        plan
+    }
+}
+
+impl PakChunk {
+    /// File name of the pak without platform suffix and extension, like `pakchunk1` or `pakchunk1_s2`
+    pub fn name(&self) -> String {
+        match self.part {
+            0 => format!("pakchunk{}", self.chunk_id),
+            part => format!("pakchunk{}_s{}", self.chunk_id, part),
+        }
+    }
+}
+
+impl ChunkPlan {
+    /// Text manifest listing which pak every file went to
+    ///
+    /// Every line contains the pak name from `PakChunk::name` followed by the file path.
+    pub fn manifest(&self) -> String {
+        let mut manifest = String::new();
+        for chunk in &self.chunks {
+            for file in &chunk.files {
+                manifest += &format!("{} {}\n", chunk.name(), file);
+            }
+        }
+        manifest
+    }
+}
+
+/// Paths of the files of an asset share their key, other files get a key of their own
+fn asset_key(path: &str) -> String {
+    let path = normalize_path(path);
+    for extension in ASSET_EXTENSIONS {
+        if let Some(stem) = path.strip_suffix(extension) {
+            // every asset key ends in an asset extension so it can't clash with other files
+            return stem.to_string() + ".uasset";
+        }
+    }
+    path
+}
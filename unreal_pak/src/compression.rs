@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+
+use crate::error::UnrealPakError;
+use crate::pakversion::PakVersion;
+
+/// Compression flags used by pak versions before compression methods were stored by name
+const COMPRESS_ZLIB: u32 = 0x01;
+const COMPRESS_GZIP: u32 = 0x02;
+const COMPRESS_CUSTOM: u32 = 0x04;
+
+/// Maximum amount of distinct compression methods a single pak can use
+pub const MAX_COMPRESSION_METHODS: usize = 5;
+
+/// Compression method of a record, identified by its name in the pak footer
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum CompressionMethod {
+    None,
+    Zlib,
+    Gzip,
+    Zstd,
+    Lz4,
+    Oodle,
+    /// Any other compression method, a codec has to be registered for it before use
+    Custom(String),
+}
+
+impl CompressionMethod {
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "" | "none" => CompressionMethod::None,
+            "zlib" => CompressionMethod::Zlib,
+            "gzip" => CompressionMethod::Gzip,
+            "zstd" => CompressionMethod::Zstd,
+            "lz4" => CompressionMethod::Lz4,
+            "oodle" => CompressionMethod::Oodle,
+            _ => CompressionMethod::Custom(name.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            CompressionMethod::None => "None",
+            CompressionMethod::Zlib => "Zlib",
+            CompressionMethod::Gzip => "Gzip",
+            CompressionMethod::Zstd => "Zstd",
+            CompressionMethod::Lz4 => "LZ4",
+            CompressionMethod::Oodle => "Oodle",
+            CompressionMethod::Custom(name) => name,
+        }
+    }
+
+    /// Resolve the compression method stored in a record header
+    ///
+    /// Version 8 and up store a 1-based index into the compression method names of the footer,
+    /// older versions store compression flags.
+    pub(crate) fn from_index(
+        index: u32,
+        compression_methods: &[CompressionMethod],
+        file_version: PakVersion,
+    ) -> Self {
+        if index == 0 {
+            return CompressionMethod::None;
+        }
+
+        if file_version >= PakVersion::PakFileVersionFnameBasedCompressionMethod {
+            return match compression_methods.get(index as usize - 1) {
+                Some(CompressionMethod::None) | None => {
+                    CompressionMethod::Custom(format!("Unknown{}", index))
+                }
+                Some(compression_method) => compression_method.clone(),
+            };
+        }
+
+        // bias flags don't affect decompression
+        match index & 0x0f {
+            COMPRESS_ZLIB => CompressionMethod::Zlib,
+            COMPRESS_GZIP => CompressionMethod::Gzip,
+            COMPRESS_CUSTOM => CompressionMethod::Oodle,
+            _ => CompressionMethod::Custom(format!("Unknown{}", index)),
+        }
+    }
+
+    /// Get the value to store in a record header for this compression method,
+    /// see `from_index`
+    pub(crate) fn to_index(
+        &self,
+        compression_methods: &[CompressionMethod],
+        file_version: PakVersion,
+    ) -> Result<u32, UnrealPakError> {
+        if *self == CompressionMethod::None {
+            return Ok(0);
+        }
+
+        if file_version >= PakVersion::PakFileVersionFnameBasedCompressionMethod {
+            return compression_methods
+                .iter()
+                .position(|e| e == self)
+                .map(|e| e as u32 + 1)
+                .ok_or_else(|| UnrealPakError::unsupported_compression(self.clone()));
+        }
+
+        match self {
+            CompressionMethod::Zlib => Ok(COMPRESS_ZLIB),
+            CompressionMethod::Gzip => Ok(COMPRESS_GZIP),
+            CompressionMethod::Oodle => Ok(COMPRESS_CUSTOM),
+            _ => Err(UnrealPakError::unsupported_compression(self.clone())),
+        }
+    }
+}
+
+/// Compressor and decompressor for a single compression method
+///
+/// Codecs operate on single compression blocks, implement this to add support for
+/// game specific methods like Oodle. Blocks are compressed from multiple threads at once.
+pub trait CompressionCodec: Send + Sync {
+    /// Compress a block, `level` is `None` for the default level of the codec
+    fn compress(&self, data: &[u8], level: Option<i32>) -> io::Result<Vec<u8>>;
+    /// Decompress a block, `decompressed_size` is the exact size of the decompressed block
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>>;
+}
+
+/// Map a compression level to the 0 to 9 range of flate2
+fn flate2_level(level: Option<i32>) -> Compression {
+    match level {
+        Some(level) => Compression::new(level.clamp(0, 9) as u32),
+        None => Compression::default(),
+    }
+}
+
+struct ZlibCodec;
+
+impl CompressionCodec for ZlibCodec {
+    fn compress(&self, data: &[u8], level: Option<i32>) -> io::Result<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2_level(level));
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(decompressed_size);
+        ZlibDecoder::new(data)
+            .take(decompressed_size as u64)
+            .read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+}
+
+struct GzipCodec;
+
+impl CompressionCodec for GzipCodec {
+    fn compress(&self, data: &[u8], level: Option<i32>) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2_level(level));
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(decompressed_size);
+        GzDecoder::new(data)
+            .take(decompressed_size as u64)
+            .read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+}
+
+struct ZstdCodec;
+
+impl CompressionCodec for ZstdCodec {
+    fn compress(&self, data: &[u8], level: Option<i32>) -> io::Result<Vec<u8>> {
+        zstd::bulk::compress(data, level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))
+    }
+
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+        zstd::bulk::decompress(data, decompressed_size)
+    }
+}
+
+struct Lz4Codec;
+
+impl CompressionCodec for Lz4Codec {
+    /// lz4_flex only has a single compression level
+    fn compress(&self, data: &[u8], _level: Option<i32>) -> io::Result<Vec<u8>> {
+        Ok(lz4_flex::block::compress(data))
+    }
+
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+        lz4_flex::block::decompress(data, decompressed_size)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Set of codecs available for reading and writing records
+#[derive(Clone)]
+pub struct CompressionRegistry {
+    codecs: HashMap<CompressionMethod, Arc<dyn CompressionCodec>>,
+}
+
+impl CompressionRegistry {
+    /// Create a registry without any codecs
+    pub fn empty() -> Self {
+        CompressionRegistry {
+            codecs: HashMap::new(),
+        }
+    }
+
+    /// Register a codec for a compression method, replacing any existing codec for it
+    pub fn register<C>(&mut self, compression_method: CompressionMethod, codec: C)
+    where
+        C: CompressionCodec + 'static,
+    {
+        self.codecs.insert(compression_method, Arc::new(codec));
+    }
+
+    pub fn get(
+        &self,
+        compression_method: &CompressionMethod,
+    ) -> Result<&dyn CompressionCodec, UnrealPakError> {
+        self.codecs
+            .get(compression_method)
+            .map(|e| e.as_ref())
+            .ok_or_else(|| UnrealPakError::unsupported_compression(compression_method.clone()))
+    }
+}
+
+impl Default for CompressionRegistry {
+    /// Registry with the built-in Zlib, Gzip, Zstd and LZ4 codecs
+    fn default() -> Self {
+        let mut registry = CompressionRegistry::empty();
+        registry.register(CompressionMethod::Zlib, ZlibCodec);
+        registry.register(CompressionMethod::Gzip, GzipCodec);
+        registry.register(CompressionMethod::Zstd, ZstdCodec);
+        registry.register(CompressionMethod::Lz4, Lz4Codec);
+        registry
+    }
+}
+
+impl fmt::Debug for CompressionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.codecs.keys()).finish()
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+
+use sha1::{Digest, Sha1};
+
+use crate::error::UnrealPakError;
+use crate::verify::hex;
+use crate::{PakFile, PakRecord};
+
+/// Records that differ between an old and a new pak
+#[derive(Debug, Clone, Default)]
+pub struct PakDelta {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Where a record of the new pak comes from when a patch is applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestEntry {
+    /// Record from the patch with the sha1 and size of its decompressed data
+    Patched { hash: Vec<u8>, size: u64 },
+    /// Record from the old pak with the sha1 of its stored data
+    Unchanged { hash: Vec<u8> },
+    /// Delete record
+    Deleted,
+}
+
+/// Every record of the new pak, written next to the patch pak and needed to apply it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchManifest {
+    pub records: BTreeMap<String, ManifestEntry>,
+}
+
+impl PatchManifest {
+    pub fn read<R: Read>(reader: R) -> Result<Self, UnrealPakError> {
+        let mut records = BTreeMap::new();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let (name, entry) =
+                parse_manifest_line(&line).ok_or_else(UnrealPakError::invalid_patch)?;
+            records.insert(name.to_string(), entry);
+        }
+        Ok(PatchManifest { records })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), UnrealPakError> {
+        for (name, entry) in &self.records {
+            match entry {
+                ManifestEntry::Patched { hash, size } => {
+                    writeln!(writer, "+ {} {} {}", hex(hash), size, name)?
+                }
+                ManifestEntry::Unchanged { hash } => writeln!(writer, "= {} {}", hex(hash), name)?,
+                ManifestEntry::Deleted => writeln!(writer, "- {}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare two paks by record content and write a patch pak with the added and changed records
+/// of `new` and delete records for the records that were removed
+///
+/// Records with the same stored data are unchanged without being read, records stored
+/// differently are compared by the sha1 of their decompressed data. The patch can be mounted
+/// on top of the old pak or turned into the new pak with `apply_patch` and the returned manifest.
+/// `patch` is written with its own version and settings, it needs version 6 or up if records were removed.
+/// Paks with records named by hash can't be compared.
+pub fn create_patch<R1, W1, R2, W2, R3, W3>(
+    old: &mut PakFile<'_, R1, W1>,
+    new: &mut PakFile<'_, R2, W2>,
+    patch: &mut PakFile<'_, R3, W3>,
+) -> Result<(PakDelta, PatchManifest), UnrealPakError>
+where
+    R1: Read + Seek,
+    W1: Write + Seek,
+    R2: Read + Seek,
+    W2: Write + Seek,
+    R3: Read + Seek,
+    W3: Write + Seek,
+{
+    old.check_named_by_hash()?;
+    new.check_named_by_hash()?;
+    let mut delta = PakDelta::default();
+    let mut manifest = PatchManifest::default();
+    patch.mount_point = new.mount_point.clone();
+
+    let names: Vec<String> = new.records.keys().cloned().collect();
+    for name in names {
+        let exists = old.records.contains_key(&name);
+        let unchanged = exists && same_content(old, new, &name)?;
+        match (unchanged, exists) {
+            (true, _) => delta.unchanged += 1,
+            (false, true) => delta.changed.push(name.clone()),
+            (false, false) => delta.added.push(name.clone()),
+        }
+
+        let entry = if new.records[&name].is_deleted() {
+            if !unchanged {
+                patch.add_record(PakRecord::new_deleted(name.clone()))?;
+            }
+            ManifestEntry::Deleted
+        } else if unchanged {
+            ManifestEntry::Unchanged {
+                hash: old.record_hash(&name)?,
+            }
+        } else {
+            let record = copy_record(new, &name)?;
+            let data = record.data.as_ref().unwrap();
+            let entry = ManifestEntry::Patched {
+                hash: Sha1::digest(data).to_vec(),
+                size: data.len() as u64,
+            };
+            patch.add_record(record)?;
+            entry
+        };
+        manifest.records.insert(name, entry);
+    }
+
+    for name in old.records.keys() {
+        if !new.records.contains_key(name) {
+            delta.removed.push(name.clone());
+            patch.add_record(PakRecord::new_deleted(name.clone()))?;
+        }
+    }
+
+    patch.write()?;
+    Ok((delta, manifest))
+}
+
+/// Rebuild the new pak from the old pak and a patch created with `create_patch`
+///
+/// Every record is checked against the manifest created along with the patch,
+/// `target` is written with its own version and settings.
+pub fn apply_patch<R1, W1, R2, W2, R3, W3>(
+    old: &mut PakFile<'_, R1, W1>,
+    patch: &mut PakFile<'_, R2, W2>,
+    manifest: &PatchManifest,
+    target: &mut PakFile<'_, R3, W3>,
+) -> Result<PakDelta, UnrealPakError>
+where
+    R1: Read + Seek,
+    W1: Write + Seek,
+    R2: Read + Seek,
+    W2: Write + Seek,
+    R3: Read + Seek,
+    W3: Write + Seek,
+{
+    old.check_named_by_hash()?;
+    patch.check_named_by_hash()?;
+    let mut delta = PakDelta::default();
+    target.mount_point = patch.mount_point.clone();
+
+    for (name, entry) in &manifest.records {
+        match (
+            patch.records.contains_key(name),
+            old.records.contains_key(name),
+        ) {
+            (false, _) => delta.unchanged += 1,
+            (true, true) => delta.changed.push(name.clone()),
+            (true, false) => delta.added.push(name.clone()),
+        }
+
+        let has_data = |records: &BTreeMap<String, PakRecord>| {
+            records.get(name).is_some_and(|e| !e.is_deleted())
+        };
+        let record = match entry {
+            ManifestEntry::Deleted => PakRecord::new_deleted(name.clone()),
+            ManifestEntry::Patched { hash, size } if has_data(&patch.records) => {
+                let record = copy_record(patch, name)?;
+                let data = record.data.as_ref().unwrap();
+                if data.len() as u64 != *size || Sha1::digest(data)[..] != hash[..] {
+                    return Err(UnrealPakError::patch_mismatch(name.clone()));
+                }
+                record
+            }
+            ManifestEntry::Unchanged { hash }
+                if has_data(&old.records) && old.record_hash(name)? == *hash =>
+            {
+                copy_record(old, name)?
+            }
+            _ => return Err(UnrealPakError::patch_mismatch(name.clone())),
+        };
+        target.add_record(record)?;
+    }
+
+    for name in old.records.keys() {
+        if !manifest.records.contains_key(name) {
+            delta.removed.push(name.clone());
+        }
+    }
+
+    target.write()?;
+    Ok(delta)
+}
+
+fn parse_manifest_line(line: &str) -> Option<(&str, ManifestEntry)> {
+    let parse_hash = |hash: &str| match hash.len() == 40 && hash.is_ascii() {
+        true => (0..hash.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hash[i..i + 2], 16).ok())
+            .collect::<Option<Vec<_>>>(),
+        false => None,
+    };
+
+    let (kind, rest) = line.split_once(' ')?;
+    match kind {
+        "+" => {
+            let mut parts = rest.splitn(3, ' ');
+            let hash = parse_hash(parts.next()?)?;
+            let size = parts.next()?.parse().ok()?;
+            Some((parts.next()?, ManifestEntry::Patched { hash, size }))
+        }
+        "=" => {
+            let (hash, name) = rest.split_once(' ')?;
+            Some((
+                name,
+                ManifestEntry::Unchanged {
+                    hash: parse_hash(hash)?,
+                },
+            ))
+        }
+        "-" => Some((rest, ManifestEntry::Deleted)),
+        _ => None,
+    }
+}
+
+/// Whether a record has the same content in both paks
+fn same_content<R1, W1, R2, W2>(
+    old: &mut PakFile<'_, R1, W1>,
+    new: &mut PakFile<'_, R2, W2>,
+    name: &String,
+) -> Result<bool, UnrealPakError>
+where
+    R1: Read + Seek,
+    W1: Write + Seek,
+    R2: Read + Seek,
+    W2: Write + Seek,
+{
+    let (a, b) = (&old.records[name], &new.records[name]);
+    if a.is_deleted() || b.is_deleted() {
+        return Ok(a.is_deleted() && b.is_deleted());
+    }
+    if a.decompressed_size != b.decompressed_size {
+        return Ok(false);
+    }
+
+    // identical stored data decompresses to identical content
+    let stored = |e: &PakRecord| (e.compressed_size, e.stored_compression_method().clone());
+    if stored(a) == stored(b) && old.record_hash(name)? == new.record_hash(name)? {
+        return Ok(true);
+    }
+    Ok(content_hash(old, name)? == content_hash(new, name)?)
+}
+
+/// Sha1 of the decompressed data of a record, streamed without loading the record into memory
+fn content_hash<R, W>(pak: &mut PakFile<'_, R, W>, name: &String) -> Result<Vec<u8>, UnrealPakError>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let mut hasher = Sha1::new();
+    match pak.records[name].data {
+        Some(ref data) => hasher.update(data),
+        None => {
+            io::copy(&mut pak.get_record_reader(name)?, &mut hasher)?;
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Read a record into a new record with the same compression settings
+fn copy_record<R, W>(
+    pak: &mut PakFile<'_, R, W>,
+    name: &String,
+) -> Result<PakRecord, UnrealPakError>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let source = &pak.records[name];
+    let compression_method = source.compression_method.clone();
+    let compression_block_size = source.compression_block_size.filter(|e| *e > 0);
+    let timestamp = source.timestamp;
+
+    let data = match source.data {
+        Some(ref data) => data.clone(),
+        None => {
+            let mut data = Vec::new();
+            pak.get_record_reader(name)?.read_to_end(&mut data)?;
+            data
+        }
+    };
+
+    let mut record = PakRecord::new(name.clone(), data, compression_method)?;
+    record.compression_block_size = compression_block_size;
+    record.timestamp = timestamp;
+    Ok(record)
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes256;
+
+use crate::error::UnrealPakError;
+
+/// Size of a single AES block, encrypted data is always padded to a multiple of this
+pub const AES_BLOCK_SIZE: u64 = 16;
+
+/// Aligns `size` up to the next multiple of `AES_BLOCK_SIZE`
+pub(crate) fn align(size: u64) -> u64 {
+    // sizes read from hostile paks may not fit, they are out of bounds either way
+    size.saturating_add(AES_BLOCK_SIZE - 1) & !(AES_BLOCK_SIZE - 1)
+}
+
+/// 256-bit AES key used for encrypting and decrypting pak data
+#[derive(Clone)]
+pub struct AesKey {
+    cipher: Aes256,
+}
+
+impl AesKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        AesKey {
+            cipher: Aes256::new(GenericArray::from_slice(&key)),
+        }
+    }
+
+    /// Parse a key from a hex string, with or without the `0x` prefix
+    pub fn from_hex(key: &str) -> Result<Self, UnrealPakError> {
+        let key = key.trim();
+        let key = key
+            .strip_prefix("0x")
+            .or_else(|| key.strip_prefix("0X"))
+            .unwrap_or(key);
+
+        if key.len() != 64 || !key.is_ascii() {
+            return Err(UnrealPakError::invalid_encryption_key());
+        }
+
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&key[i * 2..i * 2 + 2], 16)
+                .map_err(|_| UnrealPakError::invalid_encryption_key())?;
+        }
+        Ok(AesKey::new(bytes))
+    }
+
+    /// Encrypt `data` in place, `data` length must be a multiple of `AES_BLOCK_SIZE`
+    pub(crate) fn encrypt(&self, data: &mut [u8]) {
+        for block in data.chunks_exact_mut(AES_BLOCK_SIZE as usize) {
+            self.cipher
+                .encrypt_block(GenericArray::from_mut_slice(block));
+        }
+    }
+
+    /// Decrypt `data` in place, `data` length must be a multiple of `AES_BLOCK_SIZE`
+    pub(crate) fn decrypt(&self, data: &mut [u8]) {
+        for block in data.chunks_exact_mut(AES_BLOCK_SIZE as usize) {
+            self.cipher
+                .decrypt_block(GenericArray::from_mut_slice(block));
+        }
+    }
+}
+
+impl fmt::Debug for AesKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AesKey { .. }")
+    }
+}
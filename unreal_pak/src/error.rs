@@ -11,6 +11,11 @@ pub struct UnrealPakError {
 }
 
 impl UnrealPakError {
+    /// What went wrong, for matching on specific errors
+    pub fn kind(&self) -> &UnrealPakErrorKind {
+        &self.kind
+    }
+
     pub fn unsupported_pak_version(version: PakVersion) -> Self {
         UnrealPakError {
             kind: UnrealPakErrorKind::UnsupportedPakVersion(version),
@@ -21,6 +26,11 @@ impl UnrealPakError {
             kind: UnrealPakErrorKind::UnsupportedCompression(method),
         }
     }
+    pub fn too_many_compression_methods(count: usize) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::TooManyCompressionMethods(count),
+        }
+    }
     pub fn invalid_pak_file() -> Self {
         UnrealPakError {
             kind: UnrealPakErrorKind::InvalidPakFile,
@@ -31,14 +41,94 @@ impl UnrealPakError {
             kind: UnrealPakErrorKind::RecordNotFound(record_name),
         }
     }
-    pub fn enrcryption_unsupported() -> Self {
+    pub fn invalid_record() -> Self {
         UnrealPakError {
-            kind: UnrealPakErrorKind::EncryptionUnsupported,
+            kind: UnrealPakErrorKind::InvalidRecord,
         }
     }
-    pub fn invalid_record() -> Self {
+    pub fn invalid_encryption_key() -> Self {
         UnrealPakError {
-            kind: UnrealPakErrorKind::InvalidRecord,
+            kind: UnrealPakErrorKind::InvalidEncryptionKey,
+        }
+    }
+    pub fn index_hash_mismatch() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::IndexHashMismatch,
+        }
+    }
+    pub fn frozen_index_unsupported() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::FrozenIndexUnsupported,
+        }
+    }
+    pub fn named_by_hash(record_name: String) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::NamedByHash(record_name),
+        }
+    }
+    pub fn missing_directory_index() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::MissingDirectoryIndex,
+        }
+    }
+    pub fn missing_encryption_key(guid: [u8; 16]) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::MissingEncryptionKey(guid),
+        }
+    }
+    pub fn invalid_io_store() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidIoStore,
+        }
+    }
+    pub fn unsupported_io_store_version(version: u8) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::UnsupportedIoStoreVersion(version),
+        }
+    }
+    pub fn invalid_block_size(block_size: u32) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidBlockSize(block_size),
+        }
+    }
+    pub fn invalid_order_file(line: usize) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidOrderFile(line),
+        }
+    }
+    pub fn invalid_signature_file() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidSignatureFile,
+        }
+    }
+    pub fn invalid_signing_key() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidSigningKey,
+        }
+    }
+    pub fn out_of_bounds(offset: u64, size: u64) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::OutOfBounds { offset, size },
+        }
+    }
+    pub fn invalid_record_size(offset: u64) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidRecordSize(offset),
+        }
+    }
+    pub fn invalid_compression_block(offset: u64) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidCompressionBlock(offset),
+        }
+    }
+    pub fn invalid_patch() -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::InvalidPatch,
+        }
+    }
+    pub fn patch_mismatch(record_name: String) -> Self {
+        UnrealPakError {
+            kind: UnrealPakErrorKind::PatchMismatch(record_name),
         }
     }
 }
@@ -52,15 +142,66 @@ impl fmt::Display for UnrealPakError {
             UnrealPakErrorKind::UnsupportedCompression(ref method) => {
                 format!("Unsupported compression method: {:?}", method)
             }
+            UnrealPakErrorKind::TooManyCompressionMethods(count) => {
+                format!("Too many compression methods in one pak file: {}", count)
+            }
             UnrealPakErrorKind::InvalidPakFile => "Invalid pak file".to_string(),
             UnrealPakErrorKind::RecordNotFound(ref record_name) => {
                 format!("Record not found: {}", record_name)
             }
-            UnrealPakErrorKind::EncryptionUnsupported => "Encryption is not supported".to_string(),
             UnrealPakErrorKind::IoError(ref err) => {
                 format!("IO error: {}", err)
             }
             UnrealPakErrorKind::InvalidRecord => "Invalid record".to_string(),
+            UnrealPakErrorKind::InvalidEncryptionKey => "Invalid encryption key".to_string(),
+            UnrealPakErrorKind::IndexHashMismatch => "Index hash mismatch".to_string(),
+            UnrealPakErrorKind::FrozenIndexUnsupported => {
+                "Frozen pak indices are not supported".to_string()
+            }
+            UnrealPakErrorKind::NamedByHash(ref record_name) => {
+                format!("Record {} is only known by its path hash", record_name)
+            }
+            UnrealPakErrorKind::MissingDirectoryIndex => {
+                "Pak file has no full directory index".to_string()
+            }
+            UnrealPakErrorKind::MissingEncryptionKey(ref guid) => {
+                let guid: String = guid.iter().map(|e| format!("{:02x}", e)).collect();
+                format!("No encryption key provided for guid: {}", guid)
+            }
+            UnrealPakErrorKind::InvalidIoStore => "Invalid IoStore container".to_string(),
+            UnrealPakErrorKind::UnsupportedIoStoreVersion(version) => {
+                format!("Unsupported IoStore toc version: {}", version)
+            }
+            UnrealPakErrorKind::InvalidBlockSize(block_size) => {
+                format!("Invalid compression block size: {}", block_size)
+            }
+            UnrealPakErrorKind::InvalidOrderFile(line) => {
+                format!("Invalid order file entry on line {}", line)
+            }
+            UnrealPakErrorKind::InvalidSignatureFile => "Invalid pak signature file".to_string(),
+            UnrealPakErrorKind::InvalidSigningKey => "Invalid signing key".to_string(),
+            UnrealPakErrorKind::OutOfBounds { offset, size } => {
+                format!(
+                    "Data at offset {} with size {} is out of bounds",
+                    offset, size
+                )
+            }
+            UnrealPakErrorKind::InvalidRecordSize(offset) => {
+                format!("Invalid size of the record at offset {}", offset)
+            }
+            UnrealPakErrorKind::InvalidCompressionBlock(offset) => {
+                format!(
+                    "Invalid compression block in the record at offset {}",
+                    offset
+                )
+            }
+            UnrealPakErrorKind::InvalidPatch => "Invalid pak patch".to_string(),
+            UnrealPakErrorKind::PatchMismatch(ref record_name) => {
+                format!(
+                    "Patched record {} doesn't match the target pak",
+                    record_name
+                )
+            }
         };
 
         write!(f, "{}", err_msg)
@@ -75,15 +216,49 @@ impl From<io::Error> for UnrealPakError {
     }
 }
 
+impl From<UnrealPakError> for io::Error {
+    fn from(error: UnrealPakError) -> Self {
+        match error.kind {
+            UnrealPakErrorKind::IoError(error) => error,
+            _ => io::Error::other(error),
+        }
+    }
+}
+
 impl error::Error for UnrealPakError {}
 
 #[derive(Debug)]
 pub enum UnrealPakErrorKind {
     UnsupportedPakVersion(PakVersion),
     UnsupportedCompression(CompressionMethod),
+    TooManyCompressionMethods(usize),
     InvalidPakFile,
     RecordNotFound(String),
-    EncryptionUnsupported,
     IoError(io::Error),
     InvalidRecord,
+    InvalidEncryptionKey,
+    IndexHashMismatch,
+    FrozenIndexUnsupported,
+    /// Record of a pak without a full directory index, its name is the path hash
+    NamedByHash(String),
+    MissingDirectoryIndex,
+    MissingEncryptionKey([u8; 16]),
+    InvalidBlockSize(u32),
+    InvalidIoStore,
+    UnsupportedIoStoreVersion(u8),
+    InvalidOrderFile(usize),
+    InvalidSignatureFile,
+    InvalidSigningKey,
+    /// Data extends past the end of the file or index it is read from,
+    /// offsets of index entries are relative to the start of their index
+    OutOfBounds {
+        offset: u64,
+        size: u64,
+    },
+    /// Sizes of a record don't match each other or its compression blocks
+    InvalidRecordSize(u64),
+    /// Compression block of a record is malformed or decompresses to the wrong size
+    InvalidCompressionBlock(u64),
+    InvalidPatch,
+    PatchMismatch(String),
 }
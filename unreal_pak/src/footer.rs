@@ -0,0 +1,171 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::UnrealPakError;
+use crate::layout::{FooterField, PakLayout, COMPRESSION_METHOD_NAME_LEN};
+use crate::pakversion::PakVersion;
+
+/// Pak footer, also known as `FPakInfo`
+#[derive(Debug, Clone)]
+pub(crate) struct PakFooter {
+    pub encryption_key_guid: [u8; 16],
+    pub is_index_encrypted: bool,
+    pub file_version: PakVersion,
+    pub index_offset: u64,
+    pub index_size: u64,
+    pub index_hash: [u8; 20],
+    pub is_index_frozen: bool,
+    pub compression_methods: Vec<String>,
+}
+
+impl PakFooter {
+    /// Locate and read the footer at the end of a pak file
+    pub fn read<R>(reader: &mut R, layout: &PakLayout) -> Result<Self, UnrealPakError>
+    where
+        R: Read + Seek,
+    {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+
+        // the footer size depends on the version, which is stored inside of the footer,
+        // so try every version from newest to oldest until the magic lines up
+        for version in (PakVersion::PakFileVersionInitial as i32
+            ..=PakVersion::PakFileVersionFnv64bugFix as i32)
+            .rev()
+        {
+            let file_version = PakVersion::try_from(version).unwrap();
+            let footer_size = layout.footer_size(file_version);
+            let (Some(magic_offset), Some(version_offset)) = (
+                layout.footer_field_offset(FooterField::Magic, file_version),
+                layout.footer_field_offset(FooterField::Version, file_version),
+            ) else {
+                return Err(UnrealPakError::invalid_pak_file());
+            };
+            if footer_size > file_size {
+                continue;
+            }
+            let footer_offset = file_size - footer_size;
+
+            reader.seek(SeekFrom::Start(footer_offset + magic_offset))?;
+            let magic = reader.read_u32::<LittleEndian>()?;
+            reader.seek(SeekFrom::Start(footer_offset + version_offset))?;
+            let read_version = reader.read_i32::<LittleEndian>()?;
+            if magic != layout.magic || read_version != version {
+                continue;
+            }
+
+            reader.seek(SeekFrom::Start(footer_offset))?;
+            let footer = Self::read_at(reader, file_version, layout)?;
+
+            // the index is always stored in front of the footer
+            if footer
+                .index_offset
+                .checked_add(footer.index_size)
+                .is_none_or(|e| e > footer_offset)
+            {
+                return Err(UnrealPakError::out_of_bounds(
+                    footer.index_offset,
+                    footer.index_size,
+                ));
+            }
+            return Ok(footer);
+        }
+
+        Err(UnrealPakError::invalid_pak_file())
+    }
+
+    fn read_at<R>(
+        reader: &mut R,
+        file_version: PakVersion,
+        layout: &PakLayout,
+    ) -> Result<Self, UnrealPakError>
+    where
+        R: Read + Seek,
+    {
+        let mut footer = PakFooter {
+            encryption_key_guid: [0u8; 16],
+            is_index_encrypted: false,
+            file_version,
+            index_offset: 0,
+            index_size: 0,
+            index_hash: [0u8; 20],
+            is_index_frozen: false,
+            compression_methods: Vec::new(),
+        };
+
+        for field in &layout.footer_fields {
+            let Some(size) = layout.footer_field_size(*field, file_version) else {
+                continue;
+            };
+            match field {
+                FooterField::EncryptionKeyGuid => {
+                    reader.read_exact(&mut footer.encryption_key_guid)?
+                }
+                FooterField::IndexEncrypted => footer.is_index_encrypted = reader.read_u8()? != 0,
+                FooterField::Magic | FooterField::Version => {
+                    let _ = reader.read_u32::<LittleEndian>()?;
+                }
+                FooterField::IndexOffset => {
+                    footer.index_offset = reader.read_u64::<LittleEndian>()?
+                }
+                FooterField::IndexSize => footer.index_size = reader.read_u64::<LittleEndian>()?,
+                FooterField::IndexHash => reader.read_exact(&mut footer.index_hash)?,
+                FooterField::IndexFrozen => footer.is_index_frozen = reader.read_u8()? != 0,
+                FooterField::CompressionMethods => {
+                    for _ in 0..layout.compression_method_slots {
+                        let mut name = [0u8; COMPRESSION_METHOD_NAME_LEN];
+                        reader.read_exact(&mut name)?;
+                        let len = name.iter().position(|e| *e == 0).unwrap_or(name.len());
+                        footer
+                            .compression_methods
+                            .push(String::from_utf8_lossy(&name[..len]).to_string());
+                    }
+                }
+                FooterField::Padding(_) => {
+                    reader.seek(SeekFrom::Current(size as i64))?;
+                }
+            }
+        }
+
+        Ok(footer)
+    }
+
+    pub fn write<W>(&self, writer: &mut W, layout: &PakLayout) -> Result<(), UnrealPakError>
+    where
+        W: Write + Seek,
+    {
+        for field in &layout.footer_fields {
+            let Some(size) = layout.footer_field_size(*field, self.file_version) else {
+                continue;
+            };
+            match field {
+                FooterField::EncryptionKeyGuid => writer.write_all(&self.encryption_key_guid)?,
+                FooterField::IndexEncrypted => writer.write_u8(self.is_index_encrypted as u8)?,
+                FooterField::Magic => writer.write_u32::<LittleEndian>(layout.magic)?,
+                FooterField::Version => {
+                    writer.write_i32::<LittleEndian>(self.file_version.into())?
+                }
+                FooterField::IndexOffset => writer.write_u64::<LittleEndian>(self.index_offset)?,
+                FooterField::IndexSize => writer.write_u64::<LittleEndian>(self.index_size)?,
+                FooterField::IndexHash => writer.write_all(&self.index_hash)?,
+                FooterField::IndexFrozen => writer.write_u8(self.is_index_frozen as u8)?,
+                FooterField::CompressionMethods => {
+                    for i in 0..layout.compression_method_slots {
+                        let mut name = [0u8; COMPRESSION_METHOD_NAME_LEN];
+                        if let Some(compression_method) = self.compression_methods.get(i) {
+                            let bytes = compression_method.as_bytes();
+                            let len = bytes.len().min(COMPRESSION_METHOD_NAME_LEN);
+                            name[..len].copy_from_slice(&bytes[..len]);
+                        }
+                        writer.write_all(&name)?;
+                    }
+                }
+                FooterField::Padding(_) => {
+                    io::copy(&mut io::repeat(0).take(size), writer)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+/*
+Path hash index layout (version 10+)
+Primary index:
+    - mount point
+    - i32 record count
+    - u64 path hash seed
+    - u32 has path hash index
+        - i64 path hash index offset
+        - i64 path hash index size
+        - 20 bytes path hash index sha1 hash
+    - u32 has full directory index
+        - i64 full directory index offset
+        - i64 full directory index size
+        - 20 bytes full directory index sha1 hash
+    - encoded records
+        - u32 size
+        - encoded record data
+    - i32 non encoded record count
+    - non encoded records (same layout as the record header)
+
+Full directory index:
+    - i32 directory count
+    - directories
+        - directory name
+        - i32 file count
+        - files
+            - file name
+            - i32 record location
+
+Path hash index:
+    - i32 record count
+    - records
+        - u64 fnv64 hash of the lowercase utf-16 path
+        - i32 record location
+    - i32 pruned directory count (always 0)
+
+Record location:
+    - >= 0: byte offset into the encoded records
+    - < 0: -(index + 1) into the non encoded records
+*/
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::buf_ext::{BufReaderExt, BufWriterExt};
+use crate::encryption;
+use crate::error::UnrealPakError;
+use crate::layout::PakLayout;
+use crate::pakversion::PakVersion;
+use crate::{Block, CompressionMethod, PakRecord, RECORD_FLAG_ENCRYPTED};
+
+/// Offset, size and hash of a secondary index stored outside of the primary index
+#[derive(Debug, Clone)]
+pub(crate) struct SecondaryIndexInfo {
+    pub offset: u64,
+    pub size: u64,
+    pub hash: [u8; 20],
+}
+
+impl SecondaryIndexInfo {
+    pub fn write<W>(info: Option<&Self>, writer: &mut W) -> Result<(), UnrealPakError>
+    where
+        W: Write,
+    {
+        match info {
+            Some(info) => {
+                writer.write_u32::<LittleEndian>(1)?;
+                writer.write_u64::<LittleEndian>(info.offset)?;
+                writer.write_u64::<LittleEndian>(info.size)?;
+                writer.write_all(&info.hash)?;
+            }
+            None => writer.write_u32::<LittleEndian>(0)?,
+        }
+        Ok(())
+    }
+
+    pub fn read<R>(reader: &mut R) -> Result<Option<Self>, UnrealPakError>
+    where
+        R: Read,
+    {
+        if reader.read_u32::<LittleEndian>()? == 0 {
+            return Ok(None);
+        }
+
+        let offset = reader.read_i64::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()?;
+        let mut hash = [0u8; 20];
+        reader.read_exact(&mut hash)?;
+
+        // an offset of -1 marks the index as absent
+        if offset < 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(SecondaryIndexInfo {
+            offset: offset as u64,
+            size,
+            hash,
+        }))
+    }
+}
+
+/// Decode a bit-packed record from the encoded records of the primary index
+pub(crate) fn read_encoded_record<R>(
+    reader: &mut R,
+    file_version: PakVersion,
+    compression_methods: &[CompressionMethod],
+    layout: &PakLayout,
+) -> Result<PakRecord, UnrealPakError>
+where
+    R: Read,
+{
+    let value = reader.read_u32::<LittleEndian>()?;
+
+    let compression_block_size = match value & 0x3f {
+        0x3f => reader.read_u32::<LittleEndian>()?,
+        block_size => block_size << 11,
+    };
+
+    let compression_method_index = (value >> 23) & 0x3f;
+    let compression_method =
+        CompressionMethod::from_index(compression_method_index, compression_methods, file_version);
+
+    let is_offset_32_bit_safe = value & (1 << 31) != 0;
+    let is_decompressed_size_32_bit_safe = value & (1 << 30) != 0;
+    let is_size_32_bit_safe = value & (1 << 29) != 0;
+    let is_encrypted = value & (1 << 22) != 0;
+    let block_count = (value >> 6) & 0xffff;
+
+    let mut read_size = |is_32_bit_safe: bool| match is_32_bit_safe {
+        true => reader.read_u32::<LittleEndian>().map(|e| e as u64),
+        false => reader.read_u64::<LittleEndian>(),
+    };
+
+    let offset = read_size(is_offset_32_bit_safe)?;
+    let decompressed_size = read_size(is_decompressed_size_32_bit_safe)?;
+    let compressed_size = match compression_method_index {
+        0 => decompressed_size,
+        _ => read_size(is_size_32_bit_safe)?,
+    };
+
+    // single block records don't store the block size
+    let compression_block_size = match block_count {
+        1 => decompressed_size as u32,
+        _ => compression_block_size,
+    };
+
+    let mut record = PakRecord {
+        file_name: String::new(),
+        offset,
+        compressed_size,
+        decompressed_size,
+        compression_method: compression_method.clone(),
+        compression_block_size: Some(compression_block_size),
+        flags: Some(match is_encrypted {
+            true => RECORD_FLAG_ENCRYPTED,
+            false => 0,
+        }),
+        hash: vec![0u8; 20],
+        timestamp: None,
+        data: None,
+        compression_blocks: None,
+        stored: false,
+        stored_uncompressed: false,
+        named_by_hash: false,
+    };
+
+    if compression_method != CompressionMethod::None {
+        record.compression_blocks = Some(vec![Block { start: 0, size: 0 }; block_count as usize]);
+        // block offsets are relative to the record header in front of the data
+        let mut block_start = record.header_size(file_version, layout);
+        let blocks = record.compression_blocks.as_mut().unwrap();
+
+        if block_count == 1 && !is_encrypted {
+            blocks[0] = Block {
+                start: block_start,
+                size: compressed_size,
+            };
+        } else {
+            for block in blocks.iter_mut() {
+                let size = reader.read_u32::<LittleEndian>()? as u64;
+                *block = Block {
+                    start: block_start,
+                    size,
+                };
+                block_start += match is_encrypted {
+                    true => encryption::align(size),
+                    false => size,
+                };
+            }
+        }
+    }
+
+    Ok(record)
+}
+
+/// Read the full directory index, returns every file path with its record location
+pub(crate) fn read_directory_index<R>(reader: &mut R) -> Result<Vec<(String, i32)>, UnrealPakError>
+where
+    R: Read + Seek,
+{
+    let mut files = Vec::new();
+
+    let directory_count = reader.read_i32::<LittleEndian>()?;
+    for _ in 0..directory_count {
+        let directory_name = reader.read_string()?.unwrap_or_default();
+        let file_count = reader.read_i32::<LittleEndian>()?;
+        for _ in 0..file_count {
+            let file_name = reader.read_string()?.unwrap_or_default();
+            let location = reader.read_i32::<LittleEndian>()?;
+
+            let path = directory_name.clone() + &file_name;
+            files.push((path.trim_start_matches('/').to_string(), location));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Read the path hash index, returns the path hash of every file with its record location
+pub(crate) fn read_path_hash_index<R>(reader: &mut R) -> Result<Vec<(u64, i32)>, UnrealPakError>
+where
+    R: Read,
+{
+    let mut files = Vec::new();
+
+    let file_count = reader.read_i32::<LittleEndian>()?;
+    for _ in 0..file_count {
+        let hash = reader.read_u64::<LittleEndian>()?;
+        let location = reader.read_i32::<LittleEndian>()?;
+        files.push((hash, location));
+    }
+
+    Ok(files)
+}
+
+/// Encode a record into its bit-packed form, returns false if the record can't be encoded
+/// and has to be stored as a non encoded record instead, which is always the case for delete records
+pub(crate) fn write_encoded_record<W>(
+    writer: &mut W,
+    record: &PakRecord,
+    file_version: PakVersion,
+    compression_methods: &[CompressionMethod],
+) -> Result<bool, UnrealPakError>
+where
+    W: Write,
+{
+    let compression_method_index = record
+        .stored_compression_method()
+        .to_index(compression_methods, file_version)?;
+    let blocks = match record.stored_compression_method() {
+        CompressionMethod::None => &[][..],
+        _ => record.compression_blocks.as_deref().unwrap_or_default(),
+    };
+
+    if compression_method_index > 0x3f || blocks.len() > 0xffff || record.is_deleted() {
+        return Ok(false);
+    }
+
+    let compression_block_size = record.compression_block_size.unwrap_or(0);
+    let is_offset_32_bit_safe = record.offset <= u32::MAX as u64;
+    let is_decompressed_size_32_bit_safe = record.decompressed_size <= u32::MAX as u64;
+    let is_size_32_bit_safe = record.compressed_size <= u32::MAX as u64;
+
+    let mut value = match (compression_block_size >> 11) & 0x3f {
+        packed if packed << 11 == compression_block_size => packed,
+        _ => 0x3f,
+    };
+    value |= (blocks.len() as u32) << 6;
+    value |= (record.is_encrypted() as u32) << 22;
+    value |= compression_method_index << 23;
+    value |= (is_size_32_bit_safe as u32) << 29;
+    value |= (is_decompressed_size_32_bit_safe as u32) << 30;
+    value |= (is_offset_32_bit_safe as u32) << 31;
+
+    writer.write_u32::<LittleEndian>(value)?;
+    if value & 0x3f == 0x3f {
+        writer.write_u32::<LittleEndian>(compression_block_size)?;
+    }
+
+    let mut write_size = |size: u64, is_32_bit_safe: bool| match is_32_bit_safe {
+        true => writer.write_u32::<LittleEndian>(size as u32),
+        false => writer.write_u64::<LittleEndian>(size),
+    };
+
+    write_size(record.offset, is_offset_32_bit_safe)?;
+    write_size(record.decompressed_size, is_decompressed_size_32_bit_safe)?;
+    if compression_method_index != 0 {
+        write_size(record.compressed_size, is_size_32_bit_safe)?;
+    }
+
+    if blocks.len() > 1 || (blocks.len() == 1 && record.is_encrypted()) {
+        for block in blocks {
+            writer.write_u32::<LittleEndian>(block.size as u32)?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Hash a path relative to the mount point for the path hash index
+pub(crate) fn path_hash(path: &str, seed: u64, file_version: PakVersion) -> u64 {
+    // versions before the fnv64 bug fix have the offset basis and prime swapped
+    let (offset_basis, prime) = match file_version >= PakVersion::PakFileVersionFnv64bugFix {
+        true => (0xcbf29ce484222325u64, 0x00000100000001b3u64),
+        false => (0x00000100000001b3u64, 0xcbf29ce484222325u64),
+    };
+
+    let mut hash = offset_basis.wrapping_add(seed);
+    for c in path.to_lowercase().encode_utf16() {
+        for byte in c.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(prime);
+        }
+    }
+    hash
+}
+
+/// Build the path hash index from every file path with its record location
+pub(crate) fn write_path_hash_index(
+    files: &[(String, i32)],
+    seed: u64,
+    file_version: PakVersion,
+) -> Result<Vec<u8>, UnrealPakError> {
+    let mut writer = Vec::new();
+
+    writer.write_i32::<LittleEndian>(files.len() as i32)?;
+    for (path, location) in files {
+        writer.write_u64::<LittleEndian>(path_hash(path, seed, file_version))?;
+        writer.write_i32::<LittleEndian>(*location)?;
+    }
+    // pruned directory index
+    writer.write_i32::<LittleEndian>(0)?;
+
+    Ok(writer)
+}
+
+/// Build the full directory index from every file path with its record location
+pub(crate) fn write_directory_index(files: &[(String, i32)]) -> Result<Vec<u8>, UnrealPakError> {
+    let mut directories: BTreeMap<String, BTreeMap<String, i32>> = BTreeMap::new();
+
+    for (path, location) in files {
+        let (directory, file_name) = match path.rfind('/') {
+            Some(index) => (path[..index + 1].to_string(), &path[index + 1..]),
+            None => (String::from("/"), &path[..]),
+        };
+
+        // every parent directory needs an entry as well
+        let mut parent = directory.trim_end_matches('/');
+        while let Some(index) = parent.rfind('/') {
+            parent = &parent[..index];
+            directories.entry(parent.to_string() + "/").or_default();
+        }
+        directories.entry(String::from("/")).or_default();
+
+        directories
+            .entry(directory)
+            .or_default()
+            .insert(file_name.to_string(), *location);
+    }
+
+    let mut writer = std::io::Cursor::new(Vec::new());
+    writer.write_i32::<LittleEndian>(directories.len() as i32)?;
+    for (directory, files) in directories {
+        writer.write_string(Some(&directory))?;
+        writer.write_i32::<LittleEndian>(files.len() as i32)?;
+        for (file_name, location) in files {
+            writer.write_string(Some(&file_name))?;
+            writer.write_i32::<LittleEndian>(location)?;
+        }
+    }
+
+    Ok(writer.into_inner())
+}
@@ -0,0 +1,115 @@
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::buf_ext::BufReaderExt;
+use crate::error::UnrealPakError;
+
+/// Marks the end of a directory or file list and the unnamed root directory
+const INVALID_INDEX: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct DirectoryEntry {
+    name: u32,
+    first_child: u32,
+    next_sibling: u32,
+    first_file: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FileEntry {
+    name: u32,
+    next_file: u32,
+    chunk_index: u32,
+}
+
+/// Read a decrypted directory index, returns the mount point and every file path
+/// relative to it with the index of its chunk in the toc
+pub(crate) fn read_directory_index(
+    data: Vec<u8>,
+) -> Result<(String, Vec<(String, u32)>), UnrealPakError> {
+    let mut reader = Cursor::new(data);
+    let mount_point = reader.read_string()?.unwrap_or_default();
+
+    let directory_count = reader.read_u32::<LittleEndian>()?;
+    let mut directories = Vec::new();
+    for _ in 0..directory_count {
+        directories.push(DirectoryEntry {
+            name: reader.read_u32::<LittleEndian>()?,
+            first_child: reader.read_u32::<LittleEndian>()?,
+            next_sibling: reader.read_u32::<LittleEndian>()?,
+            first_file: reader.read_u32::<LittleEndian>()?,
+        });
+    }
+
+    let file_count = reader.read_u32::<LittleEndian>()?;
+    let mut files = Vec::new();
+    for _ in 0..file_count {
+        files.push(FileEntry {
+            name: reader.read_u32::<LittleEndian>()?,
+            next_file: reader.read_u32::<LittleEndian>()?,
+            chunk_index: reader.read_u32::<LittleEndian>()?,
+        });
+    }
+
+    let string_count = reader.read_u32::<LittleEndian>()?;
+    let mut strings = Vec::new();
+    for _ in 0..string_count {
+        strings.push(reader.read_string()?.unwrap_or_default());
+    }
+
+    let name = |index: u32| match index {
+        INVALID_INDEX => Ok(""),
+        index => strings
+            .get(index as usize)
+            .map(|e| e.as_str())
+            .ok_or_else(UnrealPakError::invalid_io_store),
+    };
+
+    let mut paths = Vec::with_capacity(files.len());
+    if directories.is_empty() {
+        return Ok((mount_point, paths));
+    }
+
+    // every entry can only be visited once, which also guards against cycles
+    let mut visited = 0;
+    let mut stack = vec![(0u32, String::new())];
+    while let Some((index, parent)) = stack.pop() {
+        let mut index = index;
+        while index != INVALID_INDEX {
+            visited += 1;
+            let directory = directories
+                .get(index as usize)
+                .filter(|_| visited <= directories.len() + files.len())
+                .ok_or_else(UnrealPakError::invalid_io_store)?;
+
+            let mut path = parent.clone();
+            let directory_name = name(directory.name)?;
+            if !directory_name.is_empty() {
+                path = path + directory_name + "/";
+            }
+
+            let mut file_index = directory.first_file;
+            while file_index != INVALID_INDEX {
+                visited += 1;
+                let file = files
+                    .get(file_index as usize)
+                    .filter(|_| visited <= directories.len() + files.len())
+                    .ok_or_else(UnrealPakError::invalid_io_store)?;
+                paths.push((path.clone() + name(file.name)?, file.chunk_index));
+                file_index = file.next_file;
+            }
+
+            if directory.first_child != INVALID_INDEX {
+                stack.push((directory.first_child, path));
+            }
+            index = match index {
+                // the root has no siblings
+                0 => INVALID_INDEX,
+                _ => directory.next_sibling,
+            };
+        }
+    }
+
+    Ok((mount_point, paths))
+}
@@ -0,0 +1,370 @@
+//! IoStore containers, the .utoc table of contents with its .ucas data partitions
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::compression::{CompressionCodec, CompressionMethod, CompressionRegistry};
+use crate::encryption::{self, AesKey};
+use crate::error::UnrealPakError;
+use crate::stream::MAX_PREALLOCATION;
+
+mod directory_index;
+mod toc;
+
+use toc::{IoStoreToc, CONTAINER_FLAG_ENCRYPTED};
+
+#[derive(Debug, Copy, Clone, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum IoStoreTocVersion {
+    Invalid = 0,
+    Initial = 1,
+    DirectoryIndex = 2,
+    PartitionSize = 3,
+    PerfectHash = 4,
+    PerfectHashWithOverflow = 5,
+    OnDemandMetaData = 6,
+    RemovedOnDemandMetaData = 7,
+    ReplaceIoChunkHashWithIoHash = 8,
+}
+
+/// Chunk types of UE5 containers, UE4 containers use different values
+#[derive(Debug, Copy, Clone, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum IoChunkType {
+    Invalid = 0,
+    ExportBundleData = 1,
+    BulkData = 2,
+    OptionalBulkData = 3,
+    MemoryMappedBulkData = 4,
+    ScriptObjects = 5,
+    ContainerHeader = 6,
+    ExternalFile = 7,
+    ShaderCodeLibrary = 8,
+    ShaderCode = 9,
+    PackageStoreEntry = 10,
+    DerivedData = 11,
+    EditorDerivedData = 12,
+    PackageResource = 13,
+}
+
+/// Identifier of a chunk, also known as `FIoChunkId`
+///
+/// Package chunks use the package id as `id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IoChunkId {
+    pub id: u64,
+    pub index: u16,
+    pub chunk_type: u8,
+}
+
+impl IoChunkId {
+    pub fn new(id: u64, index: u16, chunk_type: IoChunkType) -> Self {
+        IoChunkId {
+            id,
+            index,
+            chunk_type: chunk_type.into(),
+        }
+    }
+
+    pub fn from_bytes(bytes: [u8; 12]) -> Self {
+        IoChunkId {
+            id: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            // the index is stored in network byte order
+            index: u16::from_be_bytes([bytes[8], bytes[9]]),
+            chunk_type: bytes[11],
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 12] {
+        let mut bytes = [0u8; 12];
+        bytes[..8].copy_from_slice(&self.id.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.index.to_be_bytes());
+        bytes[11] = self.chunk_type;
+        bytes
+    }
+
+    /// Chunk type, `None` for types that are unknown or from UE4 containers
+    pub fn kind(&self) -> Option<IoChunkType> {
+        IoChunkType::try_from(self.chunk_type).ok()
+    }
+}
+
+impl fmt::Display for IoChunkId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single chunk in an IoStore container
+#[derive(Debug, Clone)]
+pub struct IoStoreChunk {
+    pub id: IoChunkId,
+    /// Offset in the uncompressed address space of the container
+    pub offset: u64,
+    pub size: u64,
+    /// Path relative to the mount point, only known for containers with a directory index
+    pub file_name: Option<String>,
+    pub hash: Vec<u8>,
+    pub flags: u8,
+}
+
+/// IoStore container reader over a .utoc stream and the .ucas partition streams
+///
+/// Like `PakFile`, encryption keys and codecs have to be added before calling `load`.
+#[derive(Debug)]
+pub struct IoStoreReader<R = BufReader<File>>
+where
+    R: Read + Seek,
+{
+    pub version: IoStoreTocVersion,
+    pub container_id: u64,
+    pub encryption_key_guid: [u8; 16],
+    pub mount_point: String,
+    chunks: Vec<IoStoreChunk>,
+    chunk_lookup: HashMap<IoChunkId, usize>,
+    files: BTreeMap<String, usize>,
+    toc: Option<IoStoreToc>,
+    toc_reader: Option<R>,
+    partitions: Vec<R>,
+    encryption_keys: HashMap<[u8; 16], AesKey>,
+    compression: CompressionRegistry,
+}
+
+impl IoStoreReader {
+    /// Open a .utoc file and the .ucas partitions next to it
+    pub fn open<P>(utoc: P) -> Result<Self, UnrealPakError>
+    where
+        P: AsRef<Path>,
+    {
+        let utoc = utoc.as_ref();
+        let toc_reader = BufReader::new(File::open(utoc)?);
+
+        // additional partitions are named `<name>_s1.ucas`, `<name>_s2.ucas` and so on
+        let mut partitions = vec![BufReader::new(File::open(utoc.with_extension("ucas"))?)];
+        let stem = utoc.file_stem().unwrap_or_default().to_string_lossy();
+        loop {
+            let path: PathBuf = utoc.with_file_name(format!("{}_s{}.ucas", stem, partitions.len()));
+            match File::open(path) {
+                Ok(file) => partitions.push(BufReader::new(file)),
+                Err(_) => break,
+            }
+        }
+
+        Ok(IoStoreReader::from_readers(toc_reader, partitions))
+    }
+}
+
+impl<R> IoStoreReader<R>
+where
+    R: Read + Seek,
+{
+    pub fn from_readers(toc_reader: R, partitions: Vec<R>) -> Self {
+        IoStoreReader {
+            version: IoStoreTocVersion::Invalid,
+            container_id: 0,
+            encryption_key_guid: [0u8; 16],
+            mount_point: String::new(),
+            chunks: Vec::new(),
+            chunk_lookup: HashMap::new(),
+            files: BTreeMap::new(),
+            toc: None,
+            toc_reader: Some(toc_reader),
+            partitions,
+            encryption_keys: HashMap::new(),
+            compression: CompressionRegistry::default(),
+        }
+    }
+
+    pub fn add_encryption_key(&mut self, guid: [u8; 16], key: AesKey) {
+        self.encryption_keys.insert(guid, key);
+    }
+
+    pub fn register_compression_codec<C>(&mut self, compression_method: CompressionMethod, codec: C)
+    where
+        C: CompressionCodec + 'static,
+    {
+        self.compression.register(compression_method, codec);
+    }
+
+    fn get_encryption_key(&self, toc: &IoStoreToc) -> Result<Option<&AesKey>, UnrealPakError> {
+        if toc.container_flags & CONTAINER_FLAG_ENCRYPTED == 0 {
+            return Ok(None);
+        }
+        self.encryption_keys
+            .get(&toc.encryption_key_guid)
+            .map(Some)
+            .ok_or_else(|| UnrealPakError::missing_encryption_key(toc.encryption_key_guid))
+    }
+
+    /// Read the table of contents and the directory index
+    pub fn load(&mut self) -> Result<(), UnrealPakError> {
+        let mut toc_reader = self
+            .toc_reader
+            .take()
+            .ok_or_else(UnrealPakError::invalid_io_store)?;
+        let toc = IoStoreToc::read(&mut toc_reader)?;
+
+        self.version = toc.version;
+        self.container_id = toc.container_id;
+        self.encryption_key_guid = toc.encryption_key_guid;
+
+        let mut file_names = HashMap::new();
+        if !toc.directory_index.is_empty() {
+            let mut data = toc.directory_index.clone();
+            if let Some(encryption_key) = self.get_encryption_key(&toc)? {
+                if encryption::align(data.len() as u64) != data.len() as u64 {
+                    return Err(UnrealPakError::invalid_io_store());
+                }
+                encryption_key.decrypt(&mut data);
+            }
+
+            let (mount_point, files) = directory_index::read_directory_index(data)?;
+            self.mount_point = mount_point;
+            for (path, chunk_index) in files {
+                if chunk_index as usize >= toc.chunk_ids.len() {
+                    return Err(UnrealPakError::invalid_io_store());
+                }
+                file_names.insert(chunk_index as usize, path);
+            }
+        }
+
+        self.chunks = Vec::with_capacity(toc.chunk_ids.len());
+        for (i, (id, location)) in toc
+            .chunk_ids
+            .iter()
+            .zip(toc.chunk_offset_lengths.iter())
+            .enumerate()
+        {
+            let file_name = file_names.remove(&i);
+            if let Some(ref file_name) = file_name {
+                self.files.insert(file_name.clone(), i);
+            }
+            self.chunk_lookup.insert(*id, i);
+
+            let meta = toc.chunk_metas.get(i);
+            self.chunks.push(IoStoreChunk {
+                id: *id,
+                offset: location.offset,
+                size: location.length,
+                file_name,
+                hash: meta.map(|e| e.hash.clone()).unwrap_or_default(),
+                flags: meta.map_or(0, |e| e.flags),
+            });
+        }
+
+        self.toc = Some(toc);
+        Ok(())
+    }
+
+    /// All chunks in the order of the table of contents
+    pub fn chunks(&self) -> &[IoStoreChunk] {
+        &self.chunks
+    }
+
+    /// Paths of all files in the directory index with the index of their chunk
+    pub fn files(&self) -> &BTreeMap<String, usize> {
+        &self.files
+    }
+
+    pub fn find_chunk(&self, id: &IoChunkId) -> Option<&IoStoreChunk> {
+        self.chunk_lookup.get(id).map(|e| &self.chunks[*e])
+    }
+
+    /// Read a chunk by its index in `chunks`
+    pub fn read_chunk(&mut self, index: usize) -> Result<Vec<u8>, UnrealPakError> {
+        let toc = self
+            .toc
+            .as_ref()
+            .ok_or_else(UnrealPakError::invalid_io_store)?;
+        let chunk = self
+            .chunks
+            .get(index)
+            .ok_or_else(|| UnrealPakError::record_not_found(index.to_string()))?;
+        let encryption_key = self.get_encryption_key(toc)?.cloned();
+
+        let mut data = Vec::with_capacity(chunk.size.min(MAX_PREALLOCATION) as usize);
+        if chunk.size == 0 {
+            return Ok(data);
+        }
+
+        let block_size = toc.compression_block_size as u64;
+        let first_block = chunk.offset / block_size;
+        let last_block = (chunk.offset + chunk.size - 1) / block_size;
+        let mut block_offset = chunk.offset % block_size;
+        for block_index in first_block..=last_block {
+            let block = toc
+                .compression_blocks
+                .get(block_index as usize)
+                .ok_or_else(UnrealPakError::invalid_io_store)?;
+
+            let partition = self
+                .partitions
+                .get_mut((block.offset / toc.partition_size) as usize)
+                .ok_or_else(UnrealPakError::invalid_io_store)?;
+            partition.seek(SeekFrom::Start(block.offset % toc.partition_size))?;
+
+            let compressed_size = block.compressed_size as u64;
+            let mut block_data = match encryption_key.as_ref() {
+                Some(_) => vec![0u8; encryption::align(compressed_size) as usize],
+                None => vec![0u8; compressed_size as usize],
+            };
+            partition.read_exact(&mut block_data)?;
+            if let Some(ref encryption_key) = encryption_key {
+                encryption_key.decrypt(&mut block_data);
+                block_data.truncate(compressed_size as usize);
+            }
+
+            let block_data = match block.compression_method {
+                0 => block_data,
+                method => {
+                    let name = toc
+                        .compression_methods
+                        .get(method as usize - 1)
+                        .ok_or_else(UnrealPakError::invalid_io_store)?;
+                    self.compression
+                        .get(&CompressionMethod::from_name(name))?
+                        .decompress(&block_data, block.uncompressed_size as usize)?
+                }
+            };
+
+            let remaining = chunk.size - data.len() as u64;
+            let end = (block_offset + remaining).min(block_data.len() as u64);
+            if block_offset > end {
+                return Err(UnrealPakError::invalid_io_store());
+            }
+            data.extend_from_slice(&block_data[block_offset as usize..end as usize]);
+            block_offset = 0;
+        }
+
+        if data.len() as u64 != chunk.size {
+            return Err(UnrealPakError::invalid_io_store());
+        }
+        Ok(data)
+    }
+
+    /// Read a file by its path relative to the mount point
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, UnrealPakError> {
+        let index = *self
+            .files
+            .get(path)
+            .ok_or_else(|| UnrealPakError::record_not_found(path.to_string()))?;
+        self.read_chunk(index)
+    }
+
+    /// Read a chunk by its id
+    pub fn read_chunk_by_id(&mut self, id: &IoChunkId) -> Result<Vec<u8>, UnrealPakError> {
+        let index = *self
+            .chunk_lookup
+            .get(id)
+            .ok_or_else(|| UnrealPakError::record_not_found(id.to_string()))?;
+        self.read_chunk(index)
+    }
+}
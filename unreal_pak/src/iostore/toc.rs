@@ -0,0 +1,248 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::error::UnrealPakError;
+
+use super::{IoChunkId, IoStoreTocVersion};
+
+pub(crate) const TOC_MAGIC: &[u8; 16] = b"-==--==--==--==-";
+/// Serialized size of `FIoStoreTocHeader`
+pub(crate) const TOC_HEADER_SIZE: u32 = 144;
+/// Serialized size of a single compression block entry
+pub(crate) const COMPRESSED_BLOCK_ENTRY_SIZE: u32 = 12;
+
+pub(crate) const CONTAINER_FLAG_ENCRYPTED: u8 = 0x02;
+pub(crate) const CONTAINER_FLAG_SIGNED: u8 = 0x04;
+pub(crate) const CONTAINER_FLAG_INDEXED: u8 = 0x08;
+
+/// Location of a chunk in the uncompressed address space of the container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OffsetAndLength {
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Compression block in the .ucas partitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CompressedBlock {
+    /// Offset in the .ucas partitions, partitions are laid out back to back every `partition_size` bytes
+    pub offset: u64,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    /// Index into the compression methods, 0 is uncompressed
+    pub compression_method: u8,
+}
+
+/// Per chunk hash and flags
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChunkMeta {
+    pub hash: Vec<u8>,
+    pub flags: u8,
+}
+
+/// Parsed contents of a .utoc file, also known as `FIoStoreTocResource`
+#[derive(Debug, Clone)]
+pub(crate) struct IoStoreToc {
+    pub version: IoStoreTocVersion,
+    pub container_id: u64,
+    pub encryption_key_guid: [u8; 16],
+    pub container_flags: u8,
+    pub compression_block_size: u32,
+    /// `u64::MAX` for containers with a single partition
+    pub partition_size: u64,
+    pub chunk_ids: Vec<IoChunkId>,
+    pub chunk_offset_lengths: Vec<OffsetAndLength>,
+    pub compression_blocks: Vec<CompressedBlock>,
+    pub compression_methods: Vec<String>,
+    /// Directory index as stored, encrypted if the container is encrypted
+    pub directory_index: Vec<u8>,
+    pub chunk_metas: Vec<ChunkMeta>,
+}
+
+impl IoStoreToc {
+    pub fn read<R>(reader: &mut R) -> Result<Self, UnrealPakError>
+    where
+        R: Read + Seek,
+    {
+        let mut magic = [0u8; 16];
+        reader.read_exact(&mut magic)?;
+        if &magic != TOC_MAGIC {
+            return Err(UnrealPakError::invalid_io_store());
+        }
+
+        let version = reader.read_u8()?;
+        let version = IoStoreTocVersion::try_from(version)
+            .ok()
+            .filter(|e| *e != IoStoreTocVersion::Invalid)
+            .ok_or_else(|| UnrealPakError::unsupported_io_store_version(version))?;
+        let _reserved = reader.read_u8()?;
+        let _reserved = reader.read_u16::<LittleEndian>()?;
+
+        let header_size = reader.read_u32::<LittleEndian>()?;
+        let entry_count = reader.read_u32::<LittleEndian>()?;
+        let compressed_block_entry_count = reader.read_u32::<LittleEndian>()?;
+        let compressed_block_entry_size = reader.read_u32::<LittleEndian>()?;
+        let compression_method_name_count = reader.read_u32::<LittleEndian>()?;
+        let compression_method_name_length = reader.read_u32::<LittleEndian>()?;
+        let compression_block_size = reader.read_u32::<LittleEndian>()?;
+        let directory_index_size = reader.read_u32::<LittleEndian>()?;
+        let _partition_count = reader.read_u32::<LittleEndian>()?;
+        let container_id = reader.read_u64::<LittleEndian>()?;
+        let mut encryption_key_guid = [0u8; 16];
+        reader.read_exact(&mut encryption_key_guid)?;
+        let container_flags = reader.read_u8()?;
+        let _reserved = reader.read_u8()?;
+        let _reserved = reader.read_u16::<LittleEndian>()?;
+        let perfect_hash_seeds_count = reader.read_u32::<LittleEndian>()?;
+        let mut partition_size = reader.read_u64::<LittleEndian>()?;
+        let chunks_without_perfect_hash_count = reader.read_u32::<LittleEndian>()?;
+
+        if header_size < TOC_HEADER_SIZE
+            || compressed_block_entry_size != COMPRESSED_BLOCK_ENTRY_SIZE
+            || compression_block_size == 0
+        {
+            return Err(UnrealPakError::invalid_io_store());
+        }
+        if version < IoStoreTocVersion::PartitionSize || partition_size == 0 {
+            partition_size = u64::MAX;
+        }
+        reader.seek(SeekFrom::Start(header_size as u64))?;
+
+        // counts are not trusted for allocating, the entries have to fit in the rest of the toc
+        check_remaining(reader, entry_count as u64, 12 + 10)?;
+        let mut chunk_ids = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let mut id = [0u8; 12];
+            reader.read_exact(&mut id)?;
+            chunk_ids.push(IoChunkId::from_bytes(id));
+        }
+
+        let mut chunk_offset_lengths = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let mut data = [0u8; 10];
+            reader.read_exact(&mut data)?;
+            // both values are stored as 40 bit big endian integers
+            let offset = data[..5].iter().fold(0u64, |acc, e| acc << 8 | *e as u64);
+            let length = data[5..].iter().fold(0u64, |acc, e| acc << 8 | *e as u64);
+            chunk_offset_lengths.push(OffsetAndLength { offset, length });
+        }
+
+        // chunks are looked up through a hash map instead of the perfect hash seeds
+        if version >= IoStoreTocVersion::PerfectHash {
+            reader.seek(SeekFrom::Current(perfect_hash_seeds_count as i64 * 4))?;
+        }
+        if version >= IoStoreTocVersion::PerfectHashWithOverflow {
+            reader.seek(SeekFrom::Current(
+                chunks_without_perfect_hash_count as i64 * 4,
+            ))?;
+        }
+
+        check_remaining(
+            reader,
+            compressed_block_entry_count as u64,
+            COMPRESSED_BLOCK_ENTRY_SIZE as u64,
+        )?;
+        let mut compression_blocks = Vec::with_capacity(compressed_block_entry_count as usize);
+        for _ in 0..compressed_block_entry_count {
+            let mut data = [0u8; 12];
+            reader.read_exact(&mut data)?;
+            // 40 bit offset, 24 bit sizes and the method index, all little endian
+            let offset = data[..5]
+                .iter()
+                .rev()
+                .fold(0u64, |acc, e| acc << 8 | *e as u64);
+            let compressed_size = data[5..8]
+                .iter()
+                .rev()
+                .fold(0u32, |acc, e| acc << 8 | *e as u32);
+            let uncompressed_size = data[8..11]
+                .iter()
+                .rev()
+                .fold(0u32, |acc, e| acc << 8 | *e as u32);
+            compression_blocks.push(CompressedBlock {
+                offset,
+                compressed_size,
+                uncompressed_size,
+                compression_method: data[11],
+            });
+        }
+
+        check_remaining(
+            reader,
+            compression_method_name_count as u64,
+            compression_method_name_length as u64,
+        )?;
+        let mut compression_methods = Vec::with_capacity(compression_method_name_count as usize);
+        for _ in 0..compression_method_name_count {
+            let mut name = vec![0u8; compression_method_name_length as usize];
+            reader.read_exact(&mut name)?;
+            let len = name.iter().position(|e| *e == 0).unwrap_or(name.len());
+            compression_methods.push(String::from_utf8_lossy(&name[..len]).to_string());
+        }
+
+        if container_flags & CONTAINER_FLAG_SIGNED != 0 {
+            // toc and block signatures followed by a sha1 hash for every block
+            let hash_size = reader.read_i32::<LittleEndian>()?;
+            if hash_size < 0 {
+                return Err(UnrealPakError::invalid_io_store());
+            }
+            reader.seek(SeekFrom::Current(
+                hash_size as i64 * 2 + 20 * compressed_block_entry_count as i64,
+            ))?;
+        }
+
+        let mut directory_index = Vec::new();
+        if version >= IoStoreTocVersion::DirectoryIndex
+            && container_flags & CONTAINER_FLAG_INDEXED != 0
+        {
+            check_remaining(reader, directory_index_size as u64, 1)?;
+            directory_index = vec![0u8; directory_index_size as usize];
+            reader.read_exact(&mut directory_index)?;
+        }
+
+        let hash_size = match version >= IoStoreTocVersion::ReplaceIoChunkHashWithIoHash {
+            true => 20,
+            false => 32,
+        };
+        check_remaining(reader, entry_count as u64, hash_size as u64 + 1)?;
+        let mut chunk_metas = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let mut hash = vec![0u8; hash_size];
+            reader.read_exact(&mut hash)?;
+            let flags = reader.read_u8()?;
+            chunk_metas.push(ChunkMeta { hash, flags });
+        }
+
+        Ok(IoStoreToc {
+            version,
+            container_id,
+            encryption_key_guid,
+            container_flags,
+            compression_block_size,
+            partition_size,
+            chunk_ids,
+            chunk_offset_lengths,
+            compression_blocks,
+            compression_methods,
+            directory_index,
+            chunk_metas,
+        })
+    }
+}
+
+/// Check that `count` entries of `entry_size` bytes fit in the rest of the toc
+fn check_remaining<R>(reader: &mut R, count: u64, entry_size: u64) -> Result<(), UnrealPakError>
+where
+    R: Seek,
+{
+    let position = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+
+    let size = count.saturating_mul(entry_size);
+    if size > end.saturating_sub(position) {
+        return Err(UnrealPakError::out_of_bounds(position, size));
+    }
+    Ok(())
+}
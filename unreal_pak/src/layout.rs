@@ -0,0 +1,232 @@
+use std::mem::size_of;
+
+use crate::compression::MAX_COMPRESSION_METHODS;
+use crate::pakversion::PakVersion;
+use crate::{CompressionMethod, PakRecord};
+
+/// Magic of stock paks, `FPakInfo::PakFile_Magic`
+pub const UE4_PAK_MAGIC: u32 = 0x5A6F12E1;
+
+/// Length of a single compression method name slot in the footer
+pub(crate) const COMPRESSION_METHOD_NAME_LEN: usize = 32;
+
+/// Field of a record header, stored in front of the record data and in the index
+///
+/// Fields keep the version requirements of stock paks, padding is stored in every version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordField {
+    Offset,
+    CompressedSize,
+    DecompressedSize,
+    /// Compression method index, has to come before `CompressionBlocks`
+    CompressionMethod,
+    /// Only stored by version 1
+    Timestamp,
+    Hash,
+    /// Block count and block offsets, only stored for compressed records from version 3
+    CompressionBlocks,
+    /// From version 3
+    Flags,
+    /// From version 3
+    CompressionBlockSize,
+    /// Bytes that are skipped when reading and written as zeros
+    Padding(u32),
+}
+
+/// Field of the footer at the end of a pak
+///
+/// Fields keep the version requirements of stock paks, padding is stored in every version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FooterField {
+    /// From version 7
+    EncryptionKeyGuid,
+    /// From version 4
+    IndexEncrypted,
+    Magic,
+    Version,
+    IndexOffset,
+    IndexSize,
+    IndexHash,
+    /// Only stored by version 9
+    IndexFrozen,
+    /// From version 8
+    CompressionMethods,
+    /// Bytes that are skipped when reading and written as zeros
+    Padding(u32),
+}
+
+/// Layout of the parts of a pak that games customize
+///
+/// `PakLayout::default()` is the stock layout. Games that change the magic, add fields to
+/// the footer or record headers or shuffle their order can be read and written with
+/// a custom layout set on `PakFile::layout` before loading or writing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakLayout {
+    /// Magic of the footer, written little endian
+    pub magic: u32,
+    /// Footer fields in the order they are stored, the footer ends at the end of the file
+    pub footer_fields: Vec<FooterField>,
+    /// Amount of compression method name slots in the footer, 4 in paks written by UE 4.22
+    pub compression_method_slots: usize,
+    /// Record header fields in the order they are stored
+    pub record_fields: Vec<RecordField>,
+    /// Whether version 10 and up store records in their bit-packed form in the index,
+    /// otherwise every record is stored with a full header
+    pub encode_records: bool,
+}
+
+impl Default for PakLayout {
+    fn default() -> Self {
+        PakLayout::ue4()
+    }
+}
+
+impl PakLayout {
+    /// Stock layout written by UnrealPak
+    pub fn ue4() -> Self {
+        PakLayout {
+            magic: UE4_PAK_MAGIC,
+            footer_fields: vec![
+                FooterField::EncryptionKeyGuid,
+                FooterField::IndexEncrypted,
+                FooterField::Magic,
+                FooterField::Version,
+                FooterField::IndexOffset,
+                FooterField::IndexSize,
+                FooterField::IndexHash,
+                FooterField::IndexFrozen,
+                FooterField::CompressionMethods,
+            ],
+            compression_method_slots: MAX_COMPRESSION_METHODS,
+            record_fields: vec![
+                RecordField::Offset,
+                RecordField::CompressedSize,
+                RecordField::DecompressedSize,
+                RecordField::CompressionMethod,
+                RecordField::Timestamp,
+                RecordField::Hash,
+                RecordField::CompressionBlocks,
+                RecordField::Flags,
+                RecordField::CompressionBlockSize,
+            ],
+            encode_records: true,
+        }
+    }
+
+    /// Layout written by UE 4.22, its version 8 footers only have 4 compression method slots
+    ///
+    /// Paks are loaded with this layout if their footer doesn't line up with the stock layout.
+    pub fn ue4_22() -> Self {
+        PakLayout {
+            compression_method_slots: 4,
+            ..PakLayout::ue4()
+        }
+    }
+
+    /// Serialized size of the footer for a pak version
+    pub fn footer_size(&self, file_version: PakVersion) -> u64 {
+        self.footer_fields
+            .iter()
+            .filter_map(|e| self.footer_field_size(*e, file_version))
+            .sum()
+    }
+
+    /// Offset of a field from the start of the footer, `None` if it isn't stored
+    pub(crate) fn footer_field_offset(
+        &self,
+        field: FooterField,
+        file_version: PakVersion,
+    ) -> Option<u64> {
+        let index = self.footer_fields.iter().position(|e| *e == field)?;
+        self.footer_field_size(field, file_version)?;
+        Some(
+            self.footer_fields[..index]
+                .iter()
+                .filter_map(|e| self.footer_field_size(*e, file_version))
+                .sum(),
+        )
+    }
+
+    /// Size of a footer field, `None` if the version doesn't store it
+    pub(crate) fn footer_field_size(
+        &self,
+        field: FooterField,
+        file_version: PakVersion,
+    ) -> Option<u64> {
+        let (size, stored) = match field {
+            FooterField::EncryptionKeyGuid => (
+                16,
+                file_version >= PakVersion::PakFileVersionEncryptionKeyGuid,
+            ),
+            FooterField::IndexEncrypted => {
+                (1, file_version >= PakVersion::PakFileVersionIndexEncryption)
+            }
+            FooterField::Magic | FooterField::Version => (4, true),
+            FooterField::IndexOffset | FooterField::IndexSize => (8, true),
+            FooterField::IndexHash => (20, true),
+            FooterField::IndexFrozen => (1, file_version == PakVersion::PakFileVersionFrozenIndex),
+            FooterField::CompressionMethods => (
+                (COMPRESSION_METHOD_NAME_LEN * self.compression_method_slots) as u64,
+                file_version >= PakVersion::PakFileVersionFnameBasedCompressionMethod,
+            ),
+            FooterField::Padding(size) => (size as u64, true),
+        };
+        stored.then_some(size)
+    }
+
+    /// Size of the header of a record
+    pub(crate) fn record_header_size(&self, record: &PakRecord, file_version: PakVersion) -> u64 {
+        self.record_fields
+            .iter()
+            .filter_map(|e| record_field_size(*e, record, file_version))
+            .sum()
+    }
+
+    /// Offset of a field from the start of a record header, `None` if it isn't stored
+    pub(crate) fn record_field_offset(
+        &self,
+        field: RecordField,
+        record: &PakRecord,
+        file_version: PakVersion,
+    ) -> Option<u64> {
+        let index = self.record_fields.iter().position(|e| *e == field)?;
+        record_field_size(field, record, file_version)?;
+        Some(
+            self.record_fields[..index]
+                .iter()
+                .filter_map(|e| record_field_size(*e, record, file_version))
+                .sum(),
+        )
+    }
+}
+
+/// Size of a record header field, `None` if the record doesn't store it
+pub(crate) fn record_field_size(
+    field: RecordField,
+    record: &PakRecord,
+    file_version: PakVersion,
+) -> Option<u64> {
+    let has_blocks = file_version >= PakVersion::PakFileVersionCompressionEncryption;
+    let (size, stored) = match field {
+        RecordField::Offset | RecordField::CompressedSize | RecordField::DecompressedSize => {
+            (size_of::<u64>(), true)
+        }
+        RecordField::CompressionMethod => (size_of::<u32>(), true),
+        RecordField::Timestamp => (
+            size_of::<u64>(),
+            file_version <= PakVersion::PakFileVersionInitial,
+        ),
+        RecordField::Hash => (20, true),
+        RecordField::CompressionBlocks => {
+            let block_count = record.compression_blocks.as_ref().map_or(0, |e| e.len());
+            (
+                size_of::<u32>() + size_of::<u64>() * 2 * block_count,
+                has_blocks && *record.stored_compression_method() != CompressionMethod::None,
+            )
+        }
+        RecordField::Flags => (size_of::<u8>(), has_blocks),
+        RecordField::CompressionBlockSize => (size_of::<u32>(), has_blocks),
+        RecordField::Padding(size) => (size as usize, true),
+    };
+    stored.then_some(size as u64)
+}
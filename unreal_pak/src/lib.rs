@@ -25,43 +25,87 @@ header:
     - u32 block size
 */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
-use std::mem::size_of;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 
 use buf_ext::{BufReaderExt, BufWriterExt};
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
-use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
-use num_enum::{IntoPrimitive, TryFromPrimitive};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use compression::{CompressionCodec, CompressionRegistry};
+use encryption::AesKey;
+use footer::PakFooter;
+use index::SecondaryIndexInfo;
+use layout::{PakLayout, RecordField};
+use order::PakOrder;
 use pakversion::PakVersion;
+use rayon::prelude::*;
 use sha1::{Digest, Sha1};
+use stream::PakRecordReader;
+use update::UpdateState;
 
 mod buf_ext;
+pub mod chunk;
+pub mod compression;
+pub mod delta;
+pub mod encryption;
 pub mod error;
+mod footer;
+mod index;
+pub mod iostore;
+pub mod layout;
+pub mod order;
 pub mod pakversion;
+pub mod query;
+pub mod signature;
+pub mod stream;
+pub mod update;
+pub mod verify;
+pub mod vfs;
 use error::UnrealPakError;
 
-const UE4_PAK_MAGIC: u32 = u32::from_be_bytes([0xe1, 0x12, 0x6f, 0x5a]);
+pub use compression::CompressionMethod;
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
-#[repr(i32)]
-pub enum CompressionMethod {
-    None = 0,
-    Zlib = 1,
-    BiasMemory = 2,
-    BiasSpeed = 3,
-    Unknown = 255,
-}
+const RECORD_FLAG_ENCRYPTED: u8 = 0x01;
+const RECORD_FLAG_DELETED: u8 = 0x02;
 
+/// Pak file reader or writer over any seekable stream
+///
+/// Defaults to buffered reading and writing of a `File`, see `PakFile::reader` and `PakFile::writer`.
 #[derive(Debug)]
-pub struct PakFile<'data> {
+pub struct PakFile<'data, R = BufReader<&'data File>, W = BufWriter<&'data File>>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
     pub file_version: PakVersion,
     pub mount_point: Vec<u8>,
     pub block_size: u32,
-    pub records: HashMap<String, PakRecord>,
-    reader: Option<BufReader<&'data File>>,
-    writer: Option<BufWriter<&'data File>>,
+    /// Records by name, sorted so that writing is deterministic
+    pub records: BTreeMap<String, PakRecord>,
+    /// Guid of the encryption key, only stored by version 7 and up and zero for older paks
+    pub encryption_key_guid: [u8; 16],
+    /// Seed for the path hash index, only used by version 10 and up
+    pub path_hash_seed: u64,
+    /// Whether records should be encrypted when writing, requires a key for `encryption_key_guid`
+    pub encrypt_records: bool,
+    /// Whether the index should be encrypted when writing, requires a key for `encryption_key_guid`
+    pub encrypt_index: bool,
+    /// Compression level used when writing, `None` uses the default level of each codec
+    pub compression_level: Option<i32>,
+    /// Whether records that don't get smaller when compressed are stored uncompressed, like UnrealPak does,
+    /// such records report `CompressionMethod::None` when the pak is loaded again
+    pub skip_incompressible: bool,
+    /// Order of the record data when writing, records are ordered by name if not set
+    pub order: Option<PakOrder>,
+    /// Layout of the footer and record headers, used both for reading and writing
+    pub layout: PakLayout,
+    encryption_keys: HashMap<[u8; 16], AesKey>,
+    compression: CompressionRegistry,
+    reader: Option<R>,
+    writer: Option<W>,
+    update: Option<UpdateState<W>>,
+    marker: PhantomData<&'data File>,
 }
 
 #[derive(Debug, Clone)]
@@ -70,13 +114,36 @@ pub struct PakRecord {
     pub offset: u64,
     pub compressed_size: u64,
     pub decompressed_size: u64,
+    /// Compression method the record was created with
+    ///
+    /// Records that were stored uncompressed as they didn't get smaller keep their method
+    /// until the pak is written, the method isn't stored so they report `None` once reloaded.
     pub compression_method: CompressionMethod,
+    /// Size of the compression blocks, the block size of the pak is used when writing if not set
     pub compression_block_size: Option<u32>,
     pub flags: Option<u8>,
     pub hash: Vec<u8>,
+    /// File time of the record, only stored by version 1
+    pub timestamp: Option<u64>,
     pub data: Option<Vec<u8>>,
 
     compression_blocks: Option<Vec<Block>>,
+    /// Whether the record data is already stored in the pak it was loaded from
+    stored: bool,
+    /// Whether the data was written uncompressed as it didn't get smaller,
+    /// `compression_method` keeps the method the record was created with
+    stored_uncompressed: bool,
+    /// Whether only the path hash of the record is known, `file_name` is the hash in hex
+    named_by_hash: bool,
+}
+
+/// Kind of a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakRecordKind {
+    /// Record with file data
+    File,
+    /// Delete record without data, marks the file as removed, requires version 6 and up
+    Deleted,
 }
 
 impl PakRecord {
@@ -94,120 +161,207 @@ impl PakRecord {
             compression_block_size: None,
             flags: None,
             hash: Vec::new(),
+            timestamp: None,
             compression_blocks: None,
             data: Some(uncompressed_data),
+            stored: false,
+            stored_uncompressed: false,
+            named_by_hash: false,
         };
         Ok(record)
     }
 
-    fn read_header<R>(reader: &mut R, file_version: PakVersion) -> Result<Self, UnrealPakError>
-    where
-        R: Read + Seek,
-    {
-        let file_name = reader
-            .read_string()?
-            .ok_or_else(UnrealPakError::invalid_pak_file)?;
-        let offset = reader.read_u64::<LittleEndian>()?;
-        let compressed_size = reader.read_u64::<LittleEndian>()?;
-        let decompressed_size = reader.read_u64::<LittleEndian>()?;
-        let compression_method =
-            match CompressionMethod::try_from_primitive(reader.read_i32::<LittleEndian>()?) {
-                Ok(compression_method) => compression_method,
-                Err(_) => CompressionMethod::Unknown,
-            };
+    /// Create a delete record, which removes the file from lower priority paks when mounted
+    pub fn new_deleted(file_name: String) -> Self {
+        PakRecord {
+            file_name,
+            offset: 0,
+            compressed_size: 0,
+            decompressed_size: 0,
+            compression_method: CompressionMethod::None,
+            compression_block_size: None,
+            flags: Some(RECORD_FLAG_DELETED),
+            hash: vec![0u8; 20],
+            timestamp: None,
+            compression_blocks: None,
+            data: None,
+            stored: false,
+            stored_uncompressed: false,
+            named_by_hash: false,
+        }
+    }
 
-        if file_version <= PakVersion::PakFileVersionInitial {
-            let _timestamp = reader.read_u64::<LittleEndian>()?;
+    pub fn kind(&self) -> PakRecordKind {
+        match self.is_deleted() {
+            true => PakRecordKind::Deleted,
+            false => PakRecordKind::File,
         }
+    }
 
-        let mut hash = [0u8; 20];
-        reader.read_exact(&mut hash)?;
+    pub fn is_encrypted(&self) -> bool {
+        self.flags.unwrap_or(0) & RECORD_FLAG_ENCRYPTED != 0
+    }
 
-        let mut compression_blocks = None;
-        let mut flags = None;
-        let mut compression_block_size = None;
+    pub fn is_deleted(&self) -> bool {
+        self.flags.unwrap_or(0) & RECORD_FLAG_DELETED != 0
+    }
 
-        if file_version >= PakVersion::PakFileVersionCompressionEncryption {
-            if compression_method != CompressionMethod::None {
-                let block_count = reader.read_u32::<LittleEndian>()? as usize;
-                let mut compression_blocks_inner = Vec::with_capacity(block_count);
+    /// Whether the record comes from a pak without a full directory index
+    ///
+    /// Only the hash of its path is known and `file_name` is that hash in hex, such records
+    /// can't be written to another pak as the engine would look them up by the wrong path.
+    pub fn is_named_by_hash(&self) -> bool {
+        self.named_by_hash
+    }
 
-                for _ in 0..block_count {
-                    let start_offset = reader.read_u64::<LittleEndian>()?;
-                    let end_offset = reader.read_u64::<LittleEndian>()?;
-                    compression_blocks_inner.push(Block {
-                        start: start_offset,
-                        size: end_offset - start_offset,
-                    });
-                }
-                compression_blocks = Some(compression_blocks_inner);
-            }
+    /// Number of compression blocks, 0 for uncompressed records
+    pub fn block_count(&self) -> usize {
+        match self.stored_compression_method() {
+            CompressionMethod::None => 0,
+            _ => self.compression_blocks.as_ref().map_or(0, |e| e.len()),
+        }
+    }
 
-            flags = Some(reader.read_u8()?);
-            compression_block_size = Some(reader.read_u32::<LittleEndian>()?);
+    /// Compression method the data is stored with in the pak
+    pub(crate) fn stored_compression_method(&self) -> &CompressionMethod {
+        match self.stored_uncompressed {
+            true => &CompressionMethod::None,
+            false => &self.compression_method,
         }
+    }
+
+    fn read_header<R>(
+        reader: &mut R,
+        file_version: PakVersion,
+        include_name: bool,
+        compression_methods: &[CompressionMethod],
+        layout: &PakLayout,
+    ) -> Result<Self, UnrealPakError>
+    where
+        R: Read + Seek,
+    {
+        let file_name = match include_name {
+            true => reader
+                .read_string()?
+                .ok_or_else(UnrealPakError::invalid_pak_file)?,
+            false => String::new(),
+        };
 
-        Ok(PakRecord {
+        let mut record = PakRecord {
             file_name,
-            offset,
-            compressed_size,
-            decompressed_size,
-            compression_method,
-            compression_block_size,
-            compression_blocks,
-            flags,
-            hash: hash.to_vec(),
+            offset: 0,
+            compressed_size: 0,
+            decompressed_size: 0,
+            compression_method: CompressionMethod::None,
+            compression_block_size: None,
+            flags: None,
+            hash: vec![0u8; 20],
+            timestamp: None,
+            compression_blocks: None,
             data: None,
-        })
+            stored: false,
+            stored_uncompressed: false,
+            named_by_hash: false,
+        };
+        for field in &layout.record_fields {
+            let Some(size) = layout::record_field_size(*field, &record, file_version) else {
+                continue;
+            };
+            match field {
+                RecordField::Offset => record.offset = reader.read_u64::<LittleEndian>()?,
+                RecordField::CompressedSize => {
+                    record.compressed_size = reader.read_u64::<LittleEndian>()?
+                }
+                RecordField::DecompressedSize => {
+                    record.decompressed_size = reader.read_u64::<LittleEndian>()?
+                }
+                RecordField::CompressionMethod => {
+                    record.compression_method = CompressionMethod::from_index(
+                        reader.read_u32::<LittleEndian>()?,
+                        compression_methods,
+                        file_version,
+                    )
+                }
+                RecordField::Timestamp => {
+                    record.timestamp = Some(reader.read_u64::<LittleEndian>()?)
+                }
+                RecordField::Hash => reader.read_exact(&mut record.hash)?,
+                RecordField::CompressionBlocks => {
+                    // the count is not trusted for allocating, reading fails at the end of the index
+                    let block_count = reader.read_u32::<LittleEndian>()?;
+                    let mut compression_blocks = Vec::new();
+
+                    for _ in 0..block_count {
+                        let start_offset = reader.read_u64::<LittleEndian>()?;
+                        let end_offset = reader.read_u64::<LittleEndian>()?;
+                        let size = end_offset.checked_sub(start_offset).ok_or_else(|| {
+                            UnrealPakError::invalid_compression_block(record.offset)
+                        })?;
+                        compression_blocks.push(Block {
+                            start: start_offset,
+                            size,
+                        });
+                    }
+                    record.compression_blocks = Some(compression_blocks);
+                }
+                RecordField::Flags => record.flags = Some(reader.read_u8()?),
+                RecordField::CompressionBlockSize => {
+                    record.compression_block_size = Some(reader.read_u32::<LittleEndian>()?)
+                }
+                RecordField::Padding(_) => {
+                    reader.seek(SeekFrom::Current(size as i64))?;
+                }
+            }
+        }
+
+        Ok(record)
+    }
+
+    /// Size of the record header that is stored in front of the record data
+    fn header_size(&self, file_version: PakVersion, layout: &PakLayout) -> u64 {
+        layout.record_header_size(self, file_version)
+    }
+
+    /// Size of the record data in the pak, including encryption padding
+    fn stored_size(&self) -> u64 {
+        match self.is_encrypted() {
+            true => encryption::align(self.compressed_size),
+            false => self.compressed_size,
+        }
     }
 
     fn read_data<R>(
         &mut self,
         reader: &mut R,
         file_version: PakVersion,
+        layout: &PakLayout,
+        encryption_key: Option<&AesKey>,
+        compression: &CompressionRegistry,
     ) -> Result<(), UnrealPakError>
     where
         R: Read + Seek,
     {
-        reader.seek(SeekFrom::Start(self.offset))?;
-        match self.compression_method {
-            CompressionMethod::None => {
-                self.data = Some(vec![0u8; self.decompressed_size as usize]);
-                reader.read_exact(self.data.as_mut().unwrap())?;
-                Ok(())
-            }
-            CompressionMethod::Zlib => {
-                self.data = Some(Vec::with_capacity(self.decompressed_size as usize));
-
-                let compression_blocks = self
-                    .compression_blocks
-                    .as_ref()
-                    .ok_or_else(UnrealPakError::invalid_record)?;
-                for block in compression_blocks {
-                    let offset = block.start;
-
-                    if file_version >= PakVersion::PakFileVersionRelativeChunkOffsets {
-                        reader.seek(SeekFrom::Start(self.offset + offset))?;
-                    } else {
-                        reader.seek(SeekFrom::Start(offset))?;
-                    }
-
-                    let mut compressed_data = vec![0u8; block.size as usize];
-                    reader.read_exact(&mut compressed_data)?;
-                    let mut decoder = ZlibDecoder::new(&compressed_data[..]);
-                    decoder.read_to_end(self.data.as_mut().unwrap())?;
-                }
-
-                Ok(())
-            }
-            _ => Err(UnrealPakError::unsupported_compression(
-                self.compression_method,
-            )),
-        }?;
+        let data = PakRecordReader::new(
+            reader,
+            self,
+            file_version,
+            layout,
+            encryption_key,
+            compression,
+        )?
+        .read_all()?;
+        self.data = Some(data);
         Ok(())
     }
 
-    fn write_header<W>(&self, writer: &mut W, include_name: bool) -> Result<(), UnrealPakError>
+    fn write_header<W>(
+        &self,
+        writer: &mut W,
+        include_name: bool,
+        file_version: PakVersion,
+        compression_methods: &[CompressionMethod],
+        layout: &PakLayout,
+    ) -> Result<(), UnrealPakError>
     where
         W: Write + Seek,
     {
@@ -215,157 +369,413 @@ impl PakRecord {
             writer.write_string(Some(&self.file_name))?;
         }
 
-        let begin = writer.stream_position()?;
-        writer.write_u64::<LittleEndian>(self.offset)?;
-        writer.write_u64::<LittleEndian>(self.compressed_size)?;
-        writer.write_u64::<LittleEndian>(self.decompressed_size)?;
-        writer.write_i32::<LittleEndian>(self.compression_method.into())?;
-
-        //todo: <= handling
-        writer.write_all(&self.hash)?;
-
-        let mut max_block_size = 0;
-        if self.compression_method != CompressionMethod::None {
-            writer
-                .write_u32::<LittleEndian>(self.compression_blocks.as_ref().unwrap().len() as u32)?;
-            let size = writer.stream_position()? - begin;
-            let compression_block_offset = size
-                + size_of::<u32>() as u64
-                + size_of::<u8>() as u64
-                + ((size_of::<u64>() * 2 * self.compression_blocks.as_ref().unwrap().len()) as u64);
-
-            let compression_blocks_iter = self.compression_blocks.as_ref().unwrap().iter();
-            for compression_block in compression_blocks_iter {
-                if max_block_size < compression_block.size {
-                    max_block_size = compression_block.size;
+        for field in &layout.record_fields {
+            let Some(size) = layout::record_field_size(*field, self, file_version) else {
+                continue;
+            };
+            match field {
+                RecordField::Offset => writer.write_u64::<LittleEndian>(self.offset)?,
+                RecordField::CompressedSize => {
+                    writer.write_u64::<LittleEndian>(self.compressed_size)?
+                }
+                RecordField::DecompressedSize => {
+                    writer.write_u64::<LittleEndian>(self.decompressed_size)?
+                }
+                RecordField::CompressionMethod => writer.write_u32::<LittleEndian>(
+                    self.stored_compression_method()
+                        .to_index(compression_methods, file_version)?,
+                )?,
+                RecordField::Timestamp => {
+                    writer.write_u64::<LittleEndian>(self.timestamp.unwrap_or(0))?
+                }
+                RecordField::Hash => writer.write_all(&self.hash)?,
+                RecordField::CompressionBlocks => {
+                    let compression_blocks = self.compression_blocks.as_deref().unwrap_or_default();
+                    writer.write_u32::<LittleEndian>(compression_blocks.len() as u32)?;
+                    for compression_block in compression_blocks {
+                        writer.write_u64::<LittleEndian>(compression_block.start)?;
+                        writer.write_u64::<LittleEndian>(
+                            compression_block.start + compression_block.size,
+                        )?;
+                    }
+                }
+                RecordField::Flags => writer.write_u8(self.flags.unwrap_or(0))?,
+                RecordField::CompressionBlockSize => {
+                    writer.write_u32::<LittleEndian>(self.compression_block_size.unwrap_or(0))?
+                }
+                RecordField::Padding(_) => {
+                    io::copy(&mut io::repeat(0).take(size), writer)?;
                 }
-                writer.write_u64::<LittleEndian>(
-                    compression_block.start + compression_block_offset,
-                )?;
-                writer.write_u64::<LittleEndian>(
-                    compression_block.start + compression_block.size + compression_block_offset,
-                )?;
             }
         }
 
-        writer.write_u8(0)?; // is_encrypted
-        writer.write_u32::<LittleEndian>(65536)?;
-        // writer.write_u32::<LittleEndian>(max_block_size as u32)?;
-
         Ok(())
     }
 
-    fn write<W>(&mut self, writer: &mut W, block_size: u32) -> Result<(), UnrealPakError>
-    where
-        W: Write + Seek,
-    {
-        self.offset = writer.stream_position()?;
+    /// Compress the record data into the form that is stored in the pak, returns `None`
+    /// for records that are stored uncompressed
+    ///
+    /// Blocks are compressed in parallel, the output does not depend on the amount of threads.
+    fn compress(
+        &self,
+        block_size: u32,
+        encrypt: bool,
+        compression_level: Option<i32>,
+        skip_incompressible: bool,
+        compression: &CompressionRegistry,
+    ) -> Result<Option<CompressedRecord>, UnrealPakError> {
+        if self.compression_method == CompressionMethod::None || self.is_deleted() {
+            return Ok(None);
+        }
 
-        let mut compressed_data = Vec::new();
-        let data = match self.compression_method {
-            CompressionMethod::Zlib => {
-                self.compression_blocks = Some(Vec::new());
-                let num_blocks =
-                    (self.data.as_ref().unwrap().len() as f64 / block_size as f64).ceil() as u32;
-
-                for i in 0..num_blocks {
-                    let block_start = i as u64 * block_size as u64;
-                    let mut block_end = (i + 1) as u64 * block_size as u64;
-                    if block_end > self.data.as_ref().unwrap().len() as u64 {
-                        block_end = self.data.as_ref().unwrap().len() as u64;
-                    }
+        let block_size = self
+            .compression_block_size
+            .filter(|e| *e > 0)
+            .unwrap_or(block_size);
+        if block_size == 0 {
+            return Err(UnrealPakError::invalid_block_size(block_size));
+        }
 
-                    let block_uncompressed_data =
-                        &self.data.as_ref().unwrap()[block_start as usize..block_end as usize];
+        let codec = compression.get(&self.compression_method)?;
+        let uncompressed_data = self
+            .data
+            .as_ref()
+            .ok_or_else(UnrealPakError::invalid_record)?;
+
+        let compressed_blocks = uncompressed_data
+            .par_chunks(block_size as usize)
+            .map(|block| codec.compress(block, compression_level))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut data = Vec::new();
+        let mut blocks = Vec::with_capacity(compressed_blocks.len());
+        for compressed_block in compressed_blocks {
+            blocks.push(Block {
+                start: data.len() as u64,
+                size: compressed_block.len() as u64,
+            });
+            data.extend_from_slice(&compressed_block);
+            if encrypt {
+                // each block must start on an aes block boundary
+                data.resize(encryption::align(data.len() as u64) as usize, 0);
+            }
+        }
 
-                    let begin = compressed_data.len() as u64;
+        if skip_incompressible && data.len() >= uncompressed_data.len() {
+            return Ok(None);
+        }
 
-                    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-                    encoder.write_all(block_uncompressed_data)?;
-                    let block_compressed_data = encoder.finish()?;
-                    compressed_data.extend_from_slice(&block_compressed_data);
+        // single block records use the record size as their block size
+        let block_size = (block_size as u64).min(uncompressed_data.len() as u64) as u32;
+        Ok(Some(CompressedRecord {
+            data,
+            blocks,
+            block_size,
+        }))
+    }
 
-                    self.compression_blocks.as_mut().unwrap().push(Block {
-                        start: begin,
-                        size: block_compressed_data.len() as u64,
-                    });
-                }
-                Ok(&compressed_data)
+    fn write<W>(
+        &mut self,
+        writer: &mut W,
+        compressed: Option<CompressedRecord>,
+        encryption_key: Option<&AesKey>,
+        file_version: PakVersion,
+        compression_methods: &[CompressionMethod],
+        layout: &PakLayout,
+    ) -> Result<(), UnrealPakError>
+    where
+        W: Write + Seek,
+    {
+        // delete records only exist in the index
+        if self.is_deleted() {
+            self.offset = 0;
+            self.compressed_size = 0;
+            self.decompressed_size = 0;
+            self.hash = vec![0u8; 20];
+            return Ok(());
+        }
+
+        self.offset = writer.stream_position()?;
+        self.stored_uncompressed =
+            compressed.is_none() && self.compression_method != CompressionMethod::None;
+
+        let compressed_data;
+        let data = match compressed {
+            Some(compressed) => {
+                self.compression_blocks = Some(compressed.blocks);
+                self.compression_block_size = Some(compressed.block_size);
+                compressed_data = compressed.data;
+                &compressed_data
             }
-            CompressionMethod::None => Ok(self.data.as_ref().unwrap()),
-            _ => Err(UnrealPakError::invalid_record()),
-        }?;
+            None => {
+                self.compression_blocks = None;
+                self.compression_block_size = Some(0);
+                self.data
+                    .as_ref()
+                    .ok_or_else(UnrealPakError::invalid_record)?
+            }
+        };
 
         self.decompressed_size = self.data.as_ref().unwrap().len() as u64;
         self.compressed_size = data.len() as u64;
 
+        // block starts are relative to the record, or absolute before relative chunk offsets
+        let block_base = self.header_size(file_version, layout)
+            + match file_version >= PakVersion::PakFileVersionRelativeChunkOffsets {
+                true => 0,
+                false => self.offset,
+            };
+        for block in self.compression_blocks.iter_mut().flatten() {
+            block.start += block_base;
+        }
+
         let mut hasher = Sha1::new();
-        hasher.update(&data);
+        hasher.update(data);
         self.hash = hasher.finalize().to_vec();
 
-        self.write_header(writer, false)?;
-        writer.write_all(data)?;
+        self.flags = match encryption_key {
+            Some(_) => Some(RECORD_FLAG_ENCRYPTED),
+            None => Some(0),
+        };
+
+        self.write_header(writer, false, file_version, compression_methods, layout)?;
+        match encryption_key {
+            Some(encryption_key) => {
+                let mut encrypted_data = data.clone();
+                encrypted_data.resize(encryption::align(data.len() as u64) as usize, 0);
+                encryption_key.encrypt(&mut encrypted_data);
+                writer.write_all(&encrypted_data)?;
+            }
+            None => writer.write_all(data)?,
+        }
         Ok(())
     }
 }
 
+/// Key needed to read a record, `None` for records that aren't encrypted
+fn record_encryption_key<'a>(
+    encryption_keys: &'a HashMap<[u8; 16], AesKey>,
+    encryption_key_guid: [u8; 16],
+    record: &PakRecord,
+) -> Result<Option<&'a AesKey>, UnrealPakError> {
+    match record.is_encrypted() {
+        true => encryption_keys
+            .get(&encryption_key_guid)
+            .map(Some)
+            .ok_or_else(|| UnrealPakError::missing_encryption_key(encryption_key_guid)),
+        false => Ok(None),
+    }
+}
+
+/// Pad index data for encryption if needed, hash it and then encrypt it
+fn seal_index_data(mut data: Vec<u8>, encryption_key: Option<&AesKey>) -> (Vec<u8>, [u8; 20]) {
+    if encryption_key.is_some() {
+        data.resize(encryption::align(data.len() as u64) as usize, 0);
+    }
+
+    let mut hasher = Sha1::new();
+    hasher.update(&data);
+    let hash = hasher.finalize().into();
+
+    if let Some(encryption_key) = encryption_key {
+        encryption_key.encrypt(&mut data);
+    }
+    (data, hash)
+}
+
 #[derive(Debug, Clone)]
 struct Block {
     pub start: u64,
     pub size: u64,
 }
 
+/// Compressed data of a record with its blocks, block starts are relative to the data
+struct CompressedRecord {
+    data: Vec<u8>,
+    blocks: Vec<Block>,
+    block_size: u32,
+}
+
 impl<'data> PakFile<'data> {
     pub fn reader(data: &'data File) -> Self {
-        PakFile {
-            file_version: PakVersion::PakFileVersionInvalid,
-            mount_point: "../../../".as_bytes().to_vec(),
-            block_size: 0x10000,
-            records: HashMap::new(),
-            reader: Some(BufReader::new(data)),
-            writer: None,
-        }
+        PakFile::from_reader(BufReader::new(data))
     }
 
     pub fn writer(file_version: PakVersion, writer: &'data File) -> Self {
+        PakFile::from_writer(file_version, BufWriter::new(writer))
+    }
+}
+
+impl<'data, R> PakFile<'data, R>
+where
+    R: Read + Seek,
+{
+    /// Create a reader over any seekable stream, such as a `Cursor<Vec<u8>>` for paks held in memory
+    pub fn from_reader(reader: R) -> Self {
+        PakFile::new(PakVersion::PakFileVersionInvalid, Some(reader), None)
+    }
+}
+
+impl<'data, W> PakFile<'data, BufReader<&'data File>, W>
+where
+    W: Write + Seek,
+{
+    /// Create a writer over any seekable stream, the pak is written starting at the current position
+    ///
+    /// Pass a `&mut Cursor<Vec<u8>>` to build a pak in memory.
+    pub fn from_writer(file_version: PakVersion, writer: W) -> Self {
+        PakFile::new(file_version, None, Some(writer))
+    }
+}
+
+impl<'data, R, W> PakFile<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    fn new(file_version: PakVersion, reader: Option<R>, writer: Option<W>) -> Self {
         PakFile {
             file_version,
             mount_point: "../../../".as_bytes().to_vec(),
             block_size: 0x10000,
-            records: HashMap::new(),
-            reader: None,
-            writer: Some(BufWriter::new(writer)),
+            records: BTreeMap::new(),
+            encryption_key_guid: [0u8; 16],
+            path_hash_seed: 0,
+            encrypt_records: false,
+            encrypt_index: false,
+            compression_level: None,
+            skip_incompressible: true,
+            order: None,
+            layout: PakLayout::default(),
+            encryption_keys: HashMap::new(),
+            compression: CompressionRegistry::default(),
+            reader,
+            writer,
+            update: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Register an aes key for the given encryption key guid, used both for reading and writing
+    pub fn add_encryption_key(&mut self, guid: [u8; 16], key: AesKey) {
+        self.encryption_keys.insert(guid, key);
+    }
+
+    /// Register a codec for a compression method, replacing the built-in codec if there is one
+    pub fn register_compression_codec<C>(&mut self, compression_method: CompressionMethod, codec: C)
+    where
+        C: CompressionCodec + 'static,
+    {
+        self.compression.register(compression_method, codec);
+    }
+
+    fn get_encryption_key(&self) -> Result<&AesKey, UnrealPakError> {
+        self.encryption_keys
+            .get(&self.encryption_key_guid)
+            .ok_or_else(|| UnrealPakError::missing_encryption_key(self.encryption_key_guid))
+    }
+
+    /// Read a block of index data, decrypting it if needed and checking it against its hash if given
+    fn read_index_data(
+        &mut self,
+        offset: u64,
+        size: u64,
+        hash: Option<&[u8; 20]>,
+        is_encrypted: bool,
+    ) -> Result<Vec<u8>, UnrealPakError> {
+        let index = self.read_raw_index_data(offset, size, is_encrypted)?;
+
+        if let Some(hash) = hash {
+            let mut hasher = Sha1::new();
+            hasher.update(&index);
+            if hasher.finalize()[..] != hash[..] {
+                return Err(UnrealPakError::index_hash_mismatch());
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Read a block of index data, decrypting it if needed without checking its hash
+    fn read_raw_index_data(
+        &mut self,
+        offset: u64,
+        size: u64,
+        is_encrypted: bool,
+    ) -> Result<Vec<u8>, UnrealPakError> {
+        let reader = self.reader.as_mut().unwrap();
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        if offset.checked_add(size).is_none_or(|e| e > file_size) {
+            return Err(UnrealPakError::out_of_bounds(offset, size));
+        }
+
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut index = vec![0u8; size as usize];
+        reader.read_exact(&mut index)?;
+
+        if is_encrypted {
+            self.get_encryption_key()?.decrypt(&mut index);
         }
+
+        Ok(index)
     }
 
+    /// Read the index, records of paks without a full directory index are named by path hash
     pub fn load_records(&mut self) -> Result<(), UnrealPakError> {
+        self.read_records(true)?;
+        // records added to an encrypted pak are encrypted as well
+        self.encrypt_records = self.records.values().any(|e| e.is_encrypted());
+        Ok(())
+    }
+
+    /// Read the index, index hashes are only checked if `check_index_hashes` is set
+    pub(crate) fn read_records(&mut self, check_index_hashes: bool) -> Result<(), UnrealPakError> {
         if self.reader.is_none() {
             return Err(UnrealPakError::invalid_pak_file());
         }
-        let mut reader = self.reader.as_mut().unwrap();
 
-        reader.seek(SeekFrom::End(-221))?;
-        let mut _encryption_key_guid = [0u8; 16];
-        reader.read_exact(&mut _encryption_key_guid)?;
+        let reader = self.reader.as_mut().unwrap();
+        let footer = match PakFooter::read(reader, &self.layout) {
+            // version 8 paks written by UE 4.22 have fewer compression method slots
+            Err(err) if self.layout == PakLayout::ue4() => {
+                let layout = PakLayout::ue4_22();
+                let footer = PakFooter::read(reader, &layout)
+                    .ok()
+                    .filter(|e| {
+                        e.file_version == PakVersion::PakFileVersionFnameBasedCompressionMethod
+                    })
+                    .ok_or(err)?;
+                self.layout = layout;
+                footer
+            }
+            footer => footer?,
+        };
+        self.file_version = footer.file_version;
+        self.encryption_key_guid = footer.encryption_key_guid;
+        self.encrypt_index = footer.is_index_encrypted;
 
-        let is_encrypted = reader.read_u8()?;
-        if is_encrypted != 0 {
-            return Err(UnrealPakError::enrcryption_unsupported());
+        if let Some(ref mut update) = self.update {
+            update.data_end = Some(footer.index_offset);
         }
 
-        let magic = reader.read_u32::<BigEndian>()?;
-        if magic != UE4_PAK_MAGIC {
-            return Err(UnrealPakError::invalid_pak_file());
+        // the frozen index is a memory image of the engine's index structures
+        if footer.is_index_frozen {
+            return Err(UnrealPakError::frozen_index_unsupported());
         }
 
-        let file_version = PakVersion::try_from(reader.read_i32::<LittleEndian>()?)
-            .map_err(|_| UnrealPakError::invalid_pak_file())?;
-        self.file_version = file_version;
+        let index = self.read_index_data(
+            footer.index_offset,
+            footer.index_size,
+            check_index_hashes.then_some(&footer.index_hash),
+            footer.is_index_encrypted,
+        )?;
+        let mut reader = Cursor::new(index);
 
-        let index_offset = reader.read_u64::<LittleEndian>()?;
-        let _index_size = reader.read_u64::<LittleEndian>()?;
-
-        reader.seek(SeekFrom::Start(index_offset))?;
+        let compression_methods: Vec<_> = footer
+            .compression_methods
+            .iter()
+            .map(|e| CompressionMethod::from_name(e))
+            .collect();
 
         let mount_point = reader.read_string()?;
         if let Some(mount_point) = mount_point {
@@ -373,14 +783,122 @@ impl<'data> PakFile<'data> {
         }
 
         let record_count = reader.read_u32::<LittleEndian>()?;
+
+        if self.file_version >= PakVersion::PakFileVersionPathHashIndex {
+            return self.load_path_hash_index(
+                &mut reader,
+                &footer,
+                &compression_methods,
+                check_index_hashes,
+            );
+        }
+
         for _ in 0..record_count {
-            let record = PakRecord::read_header(&mut reader, file_version)?;
+            let mut record = PakRecord::read_header(
+                &mut reader,
+                self.file_version,
+                true,
+                &compression_methods,
+                &self.layout,
+            )?;
+            record.stored = true;
             self.records.insert(record.file_name.clone(), record);
         }
         Ok(())
     }
 
-    pub fn add_record(&mut self, record: PakRecord) -> Result<(), UnrealPakError> {
+    fn load_path_hash_index(
+        &mut self,
+        reader: &mut Cursor<Vec<u8>>,
+        footer: &PakFooter,
+        compression_methods: &[CompressionMethod],
+        check_index_hashes: bool,
+    ) -> Result<(), UnrealPakError> {
+        self.path_hash_seed = reader.read_u64::<LittleEndian>()?;
+        let path_hash_index = SecondaryIndexInfo::read(reader)?;
+        let full_directory_index = SecondaryIndexInfo::read(reader)?;
+
+        let encoded_records_size = reader.read_u32::<LittleEndian>()? as u64;
+        let encoded_records_offset = reader.position();
+        let mut encoded_records = Vec::new();
+        reader
+            .take(encoded_records_size)
+            .read_to_end(&mut encoded_records)?;
+        if encoded_records.len() as u64 != encoded_records_size {
+            return Err(UnrealPakError::out_of_bounds(
+                encoded_records_offset,
+                encoded_records_size,
+            ));
+        }
+
+        let non_encoded_record_count = reader.read_i32::<LittleEndian>()?;
+        let mut non_encoded_records = Vec::new();
+        for _ in 0..non_encoded_record_count {
+            non_encoded_records.push(PakRecord::read_header(
+                reader,
+                self.file_version,
+                false,
+                compression_methods,
+                &self.layout,
+            )?);
+        }
+
+        // paks shipped without the full directory index only store path hashes,
+        // their records are named by the hash as the paths can't be recovered
+        let named_by_hash = full_directory_index.is_none();
+        let files = match (full_directory_index, path_hash_index) {
+            (Some(full_directory_index), _) => {
+                let directory_index = self.read_index_data(
+                    full_directory_index.offset,
+                    full_directory_index.size,
+                    check_index_hashes.then_some(&full_directory_index.hash),
+                    footer.is_index_encrypted,
+                )?;
+                index::read_directory_index(&mut Cursor::new(directory_index))?
+            }
+            (None, Some(path_hash_index)) => {
+                let path_hash_index = self.read_index_data(
+                    path_hash_index.offset,
+                    path_hash_index.size,
+                    check_index_hashes.then_some(&path_hash_index.hash),
+                    footer.is_index_encrypted,
+                )?;
+                index::read_path_hash_index(&mut Cursor::new(path_hash_index))?
+                    .into_iter()
+                    .map(|(hash, location)| (format!("{:016x}", hash), location))
+                    .collect()
+            }
+            (None, None) => return Err(UnrealPakError::missing_directory_index()),
+        };
+
+        let mut encoded_reader = Cursor::new(encoded_records);
+        for (file_name, location) in files {
+            let mut record = match location {
+                0.. => {
+                    encoded_reader.set_position(location as u64);
+                    index::read_encoded_record(
+                        &mut encoded_reader,
+                        self.file_version,
+                        compression_methods,
+                        &self.layout,
+                    )?
+                }
+                _ => non_encoded_records
+                    .get((-(location as i64) - 1) as usize)
+                    .cloned()
+                    .ok_or_else(UnrealPakError::invalid_record)?,
+            };
+            record.file_name = file_name;
+            record.stored = true;
+            record.named_by_hash = named_by_hash;
+            self.records.insert(record.file_name.clone(), record);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_record(&mut self, mut record: PakRecord) -> Result<(), UnrealPakError> {
+        record.stored = false;
         self.records.remove(&record.file_name);
         self.records.insert(record.file_name.clone(), record);
         Ok(())
@@ -391,21 +909,205 @@ impl<'data> PakFile<'data> {
             .records
             .get_mut(name)
             .ok_or_else(|| UnrealPakError::record_not_found(name.clone()))?;
-        if record.data.is_none() {
-            record.read_data(self.reader.as_mut().unwrap(), self.file_version)?;
+        if record.data.is_none() && !record.is_deleted() {
+            let encryption_key =
+                record_encryption_key(&self.encryption_keys, self.encryption_key_guid, record)?;
+            record.read_data(
+                self.reader.as_mut().unwrap(),
+                self.file_version,
+                &self.layout,
+                encryption_key,
+                &self.compression,
+            )?;
         }
         Ok(record)
     }
 
+    /// Get a `Read + Seek` view over the data of a record without loading it into memory
+    ///
+    /// Blocks are decompressed on demand, the record data is not cached on the record.
+    pub fn get_record_reader(
+        &mut self,
+        name: &String,
+    ) -> Result<PakRecordReader<'_, R>, UnrealPakError> {
+        let record = self
+            .records
+            .get(name)
+            .ok_or_else(|| UnrealPakError::record_not_found(name.clone()))?;
+        let encryption_key =
+            record_encryption_key(&self.encryption_keys, self.encryption_key_guid, record)?;
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(UnrealPakError::invalid_pak_file)?;
+        PakRecordReader::new(
+            reader,
+            record,
+            self.file_version,
+            &self.layout,
+            encryption_key,
+            &self.compression,
+        )
+    }
+
+    /// Like `get_record_reader`, but reads the record through another reader over the same pak
+    ///
+    /// Only borrows the pak immutably, so records can be read from many threads at once
+    /// with a reader for each thread, like a separately opened `File`.
+    pub fn get_record_reader_from<'a, S>(
+        &'a self,
+        name: &String,
+        reader: &'a mut S,
+    ) -> Result<PakRecordReader<'a, S>, UnrealPakError>
+    where
+        S: Read + Seek,
+    {
+        let record = self
+            .records
+            .get(name)
+            .ok_or_else(|| UnrealPakError::record_not_found(name.clone()))?;
+        let encryption_key =
+            record_encryption_key(&self.encryption_keys, self.encryption_key_guid, record)?;
+        PakRecordReader::new(
+            reader,
+            record,
+            self.file_version,
+            &self.layout,
+            encryption_key,
+            &self.compression,
+        )
+    }
+
+    /// Sha1 of the stored data of a record, all zeros for delete records
+    ///
+    /// Encoded records of version 10 and up don't store their hash in the index,
+    /// it is read from the record header in front of the data instead.
+    pub fn record_hash(&mut self, name: &String) -> Result<Vec<u8>, UnrealPakError> {
+        let record = self
+            .records
+            .get(name)
+            .ok_or_else(|| UnrealPakError::record_not_found(name.clone()))?;
+        if record.is_deleted() || record.hash.iter().any(|e| *e != 0) || !record.stored {
+            return Ok(record.hash.clone());
+        }
+
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(UnrealPakError::invalid_pak_file)?;
+        reader.seek(SeekFrom::Start(record.offset))?;
+        Ok(PakRecord::read_header(reader, self.file_version, false, &[], &self.layout)?.hash)
+    }
+
+    /// Paths of records named by hash are unknown, so they can't be written to an index
+    pub(crate) fn check_named_by_hash(&self) -> Result<(), UnrealPakError> {
+        match self.records.values().find(|e| e.is_named_by_hash()) {
+            Some(record) => Err(UnrealPakError::named_by_hash(record.file_name.clone())),
+            None => Ok(()),
+        }
+    }
+
     pub fn write(&mut self) -> Result<(), UnrealPakError> {
         if self.writer.is_none() {
             return Err(UnrealPakError::invalid_pak_file());
         }
 
+        let encryption_key = match self.encrypt_records {
+            true => Some(self.get_encryption_key()?.clone()),
+            false => None,
+        };
+        let index_encryption_key = match self.encrypt_index {
+            true => Some(self.get_encryption_key()?.clone()),
+            false => None,
+        };
+
+        // every distinct compression method gets a slot in the footer
+        let mut compression_methods = Vec::new();
+        for record in self.records.values() {
+            if record.compression_method != CompressionMethod::None
+                && !compression_methods.contains(&record.compression_method)
+            {
+                compression_methods.push(record.compression_method.clone());
+            }
+        }
+        self.check_named_by_hash()?;
+        if self.file_version >= PakVersion::PakFileVersionLast
+            || (self.file_version < PakVersion::PakFileVersionDeleteRecords
+                && self.records.values().any(|e| e.is_deleted()))
+        {
+            return Err(UnrealPakError::unsupported_pak_version(self.file_version));
+        }
+        // compression and record encryption need the block list and flags of version 3,
+        // the footer can only mark the index as encrypted from version 4 and only stores
+        // the key guid from version 7, older paks are always read with the zero guid
+        if (self.file_version < PakVersion::PakFileVersionCompressionEncryption
+            && (self.encrypt_records || !compression_methods.is_empty()))
+            || (self.file_version < PakVersion::PakFileVersionIndexEncryption && self.encrypt_index)
+            || (self.file_version < PakVersion::PakFileVersionEncryptionKeyGuid
+                && self.encryption_key_guid != [0u8; 16])
+        {
+            return Err(UnrealPakError::unsupported_pak_version(self.file_version));
+        }
+        if self.file_version >= PakVersion::PakFileVersionFnameBasedCompressionMethod
+            && compression_methods.len() > self.layout.compression_method_slots
+        {
+            return Err(UnrealPakError::too_many_compression_methods(
+                compression_methods.len(),
+            ));
+        }
+
         let mut writer = self.writer.as_mut().unwrap();
 
-        for record in &mut self.records.values_mut() {
-            record.write(&mut writer, self.block_size)?;
+        // updates only write new records, after the stored records in place of the old index
+        let is_update = self.update.is_some();
+        if let Some(ref update) = self.update {
+            let data_end = update
+                .data_end
+                .ok_or_else(UnrealPakError::invalid_pak_file)?;
+            writer.seek(SeekFrom::Start(data_end))?;
+        }
+
+        // records are compressed in parallel in batches to limit memory usage,
+        // but always written in order so the output doesn't depend on the amount of threads
+        let mut records = self
+            .records
+            .values_mut()
+            .filter(|e| !(is_update && e.stored))
+            .collect::<Vec<_>>();
+        if let Some(ref order) = self.order {
+            // order files list paths with the mount point, the stable sort keeps records
+            // with the same priority ordered by name
+            let mount_point = String::from_utf8_lossy(&self.mount_point).replace('\\', "/");
+            records.sort_by_key(|record| {
+                order
+                    .priority(&mount_point, &record.file_name)
+                    .unwrap_or(u64::MAX)
+            });
+        }
+        for batch in records.chunks_mut(rayon::current_num_threads().max(1)) {
+            let compressed_records = batch
+                .par_iter()
+                .map(|record| {
+                    record.compress(
+                        self.block_size,
+                        encryption_key.is_some(),
+                        self.compression_level,
+                        self.skip_incompressible,
+                        &self.compression,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (record, compressed) in batch.iter_mut().zip(compressed_records) {
+                record.write(
+                    &mut writer,
+                    compressed,
+                    encryption_key.as_ref(),
+                    self.file_version,
+                    &compression_methods,
+                    &self.layout,
+                )?;
+            }
         }
 
         let index_offset = writer.stream_position()?;
@@ -415,51 +1117,130 @@ impl<'data> PakFile<'data> {
         header_writer.write_string(Some(&String::from_utf8_lossy(&self.mount_point)))?;
         header_writer.write_i32::<LittleEndian>(self.records.len() as i32)?;
 
-        for record in self.records.values_mut() {
-            record.write_header(&mut header_writer, true)?;
-        }
-        header_writer.flush()?;
-        writer.write_all(header_writer.get_ref())?;
+        let mut secondary_indices = Vec::new();
+        if self.file_version >= PakVersion::PakFileVersionPathHashIndex {
+            let mut encoded_records = Vec::new();
+            let mut non_encoded_records = Cursor::new(Vec::new());
+            let mut non_encoded_record_count = 0;
+
+            let mut files = Vec::with_capacity(self.records.len());
+            for record in self.records.values() {
+                let location = encoded_records.len() as i32;
+                if self.layout.encode_records
+                    && index::write_encoded_record(
+                        &mut encoded_records,
+                        record,
+                        self.file_version,
+                        &compression_methods,
+                    )?
+                {
+                    files.push((record.file_name.clone(), location));
+                } else {
+                    record.write_header(
+                        &mut non_encoded_records,
+                        false,
+                        self.file_version,
+                        &compression_methods,
+                        &self.layout,
+                    )?;
+                    non_encoded_record_count += 1;
+                    files.push((record.file_name.clone(), -non_encoded_record_count));
+                }
+            }
 
-        let index_length = writer.stream_position()? - index_offset;
+            let path_hash_index =
+                index::write_path_hash_index(&files, self.path_hash_seed, self.file_version)?;
+            let (path_hash_index, path_hash_index_hash) =
+                seal_index_data(path_hash_index, index_encryption_key.as_ref());
 
-        if self.file_version >= PakVersion::PakFileVersionEncryptionKeyGuid {
-            writer.write_all(&[0u8; 16])?;
-        }
-        writer.write_u8(0)?; // is_encrypted
-        writer.write_u32::<BigEndian>(UE4_PAK_MAGIC)?;
+            let directory_index = index::write_directory_index(&files)?;
+            let (directory_index, directory_index_hash) =
+                seal_index_data(directory_index, index_encryption_key.as_ref());
 
-        let file_version: i32 = self.file_version.into();
-        writer.write_i32::<LittleEndian>(file_version)?;
-        writer.write_u64::<LittleEndian>(index_offset)?;
-        writer.write_u64::<LittleEndian>(index_length)?;
+            header_writer.write_u64::<LittleEndian>(self.path_hash_seed)?;
 
-        let header = header_writer.get_ref();
-        let mut hasher = Sha1::new();
-        hasher.update(&header);
-        let hash = hasher.finalize().to_vec();
-        writer.write_all(&hash)?;
+            // the secondary indices are written right after the primary index,
+            // so their offsets are only known once the primary index size is known
+            let secondary_index_info_offset = header_writer.stream_position()?;
+            let mut path_hash_index_info = SecondaryIndexInfo {
+                offset: 0,
+                size: path_hash_index.len() as u64,
+                hash: path_hash_index_hash,
+            };
+            let mut directory_index_info = SecondaryIndexInfo {
+                offset: 0,
+                size: directory_index.len() as u64,
+                hash: directory_index_hash,
+            };
+            SecondaryIndexInfo::write(Some(&path_hash_index_info), &mut header_writer)?;
+            SecondaryIndexInfo::write(Some(&directory_index_info), &mut header_writer)?;
 
-        let compression_method = self
-            .records
-            .values()
-            .next()
-            .map(|e| e.compression_method)
-            .unwrap_or(CompressionMethod::None);
-
-        match compression_method {
-            CompressionMethod::Zlib => {
-                writer.write_all(b"Zlib")?;
-                Ok(())
+            header_writer.write_u32::<LittleEndian>(encoded_records.len() as u32)?;
+            header_writer.write_all(&encoded_records)?;
+            header_writer.write_i32::<LittleEndian>(non_encoded_record_count)?;
+            header_writer.write_all(non_encoded_records.get_ref())?;
+
+            let mut primary_index_size = header_writer.get_ref().len() as u64;
+            if index_encryption_key.is_some() {
+                primary_index_size = encryption::align(primary_index_size);
             }
-            CompressionMethod::None => {
-                writer.write_all(&[0u8; 4])?;
-                Ok(())
+            path_hash_index_info.offset = index_offset + primary_index_size;
+            directory_index_info.offset = path_hash_index_info.offset + path_hash_index_info.size;
+
+            header_writer.set_position(secondary_index_info_offset);
+            SecondaryIndexInfo::write(Some(&path_hash_index_info), &mut header_writer)?;
+            SecondaryIndexInfo::write(Some(&directory_index_info), &mut header_writer)?;
+
+            secondary_indices.push(path_hash_index);
+            secondary_indices.push(directory_index);
+        } else {
+            for record in self.records.values() {
+                record.write_header(
+                    &mut header_writer,
+                    true,
+                    self.file_version,
+                    &compression_methods,
+                    &self.layout,
+                )?;
             }
-            _ => Err(UnrealPakError::unsupported_compression(compression_method)),
-        }?;
+        }
+        header_writer.flush()?;
+
+        let (header, hash) =
+            seal_index_data(header_writer.into_inner(), index_encryption_key.as_ref());
+        writer.write_all(&header)?;
 
-        writer.write_all(&[0u8; 0x9c])?;
+        let index_length = writer.stream_position()? - index_offset;
+
+        for secondary_index in secondary_indices {
+            writer.write_all(&secondary_index)?;
+        }
+
+        let footer = PakFooter {
+            encryption_key_guid: self.encryption_key_guid,
+            is_index_encrypted: self.encrypt_index,
+            file_version: self.file_version,
+            index_offset,
+            index_size: index_length,
+            index_hash: hash,
+            is_index_frozen: false,
+            compression_methods: compression_methods
+                .iter()
+                .map(|e| e.name().to_string())
+                .collect(),
+        };
+        footer.write(writer, &self.layout)?;
+        writer.flush()?;
+
+        // the old footer may lie past the new one, the footer has to be at the end of the stream
+        if let Some(ref mut update) = self.update {
+            let end = writer.stream_position()?;
+            (update.set_len)(writer, end)?;
+            update.data_end = Some(index_offset);
+            for record in self.records.values_mut() {
+                record.stored = true;
+            }
+        }
 
         Ok(())
     }
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use crate::error::UnrealPakError;
+use crate::vfs::{join_path, normalize_path};
+
+/// Order in which records are written to a pak, in the format of UnrealPak order files
+///
+/// Every line contains a path including the mount point such as `"../../../Game/Content/Map.umap"`,
+/// optionally in quotes, followed by an optional priority. Lines without a priority use their line number. Records with a lower priority are
+/// written first, records that are not listed are written last.
+#[derive(Debug, Clone, Default)]
+pub struct PakOrder {
+    priorities: HashMap<String, u64>,
+}
+
+impl PakOrder {
+    pub fn new() -> Self {
+        PakOrder::default()
+    }
+
+    /// Parse the contents of an order file
+    pub fn parse(text: &str) -> Result<Self, UnrealPakError> {
+        let mut order = PakOrder::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (path, rest) = match line.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted
+                        .find('"')
+                        .ok_or_else(|| UnrealPakError::invalid_order_file(line_number + 1))?;
+                    (&quoted[..end], &quoted[end + 1..])
+                }
+                None => match line.find(char::is_whitespace) {
+                    Some(end) => (&line[..end], &line[end..]),
+                    None => (line, ""),
+                },
+            };
+
+            let priority = match rest.trim() {
+                "" => line_number as u64,
+                priority => priority
+                    .parse()
+                    .map_err(|_| UnrealPakError::invalid_order_file(line_number + 1))?,
+            };
+
+            order.insert(path, priority);
+        }
+
+        Ok(order)
+    }
+
+    /// Set the priority of a path, paths may include the mount point
+    pub fn insert(&mut self, path: &str, priority: u64) {
+        self.priorities.insert(normalize_path(path), priority);
+    }
+
+    /// Get the priority of a record in a pak with the given mount point
+    pub fn priority(&self, mount_point: &str, record_name: &str) -> Option<u64> {
+        self.priorities
+            .get(&normalize_path(&join_path(mount_point, record_name)))
+            .copied()
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+use crate::vfs::normalize_path;
+use crate::PakFile;
+
+/// Case insensitive index over the record names of a pak for path lookups, globs and directory listings
+///
+/// Paths are relative to the mount point, paths that include the mount point of the pak
+/// are accepted as well. The index is a snapshot, it has to be rebuilt after records are added.
+#[derive(Debug, Clone, Default)]
+pub struct RecordIndex {
+    mount_point: String,
+    /// Record names by their normalized name
+    names: BTreeMap<String, String>,
+}
+
+/// A file or directory in a `RecordIndex` directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakDirEntry {
+    pub name: String,
+    /// Record name of files, path relative to the mount point of directories
+    pub path: String,
+    pub is_directory: bool,
+}
+
+impl RecordIndex {
+    pub fn new<R, W>(pak: &PakFile<'_, R, W>) -> Self
+    where
+        R: Read + Seek,
+        W: Write + Seek,
+    {
+        let mut mount_point = normalize_path(&String::from_utf8_lossy(&pak.mount_point));
+        if !mount_point.is_empty() && !mount_point.ends_with('/') {
+            mount_point.push('/');
+        }
+
+        let mut names = BTreeMap::new();
+        for name in pak.records.keys() {
+            // names that only differ in case resolve to the first one
+            names
+                .entry(normalize_path(name))
+                .or_insert_with(|| name.clone());
+        }
+        RecordIndex { mount_point, names }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Record name for a path
+    pub fn find(&self, path: &str) -> Option<&str> {
+        self.names.get(&self.relative(path)).map(|e| e.as_str())
+    }
+
+    /// Record names matching a glob pattern, sorted case insensitive
+    ///
+    /// `?` matches a single character and `*` any characters within a directory,
+    /// `**` matches any number of directories like in `Game/Content/**/*.uasset`.
+    pub fn glob(&self, pattern: &str) -> Vec<&str> {
+        let pattern = self.relative(pattern);
+        let pattern_segments: Vec<&str> = pattern.split('/').collect();
+
+        // only names starting with the literal part of the pattern can match
+        let prefix_len = pattern.find(['*', '?']).unwrap_or(pattern.len());
+        let prefix = &pattern[..pattern[..prefix_len].rfind('/').map_or(0, |e| e + 1)];
+
+        self.names
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(key, _)| glob_matches(&pattern_segments, key))
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+
+    /// Files and directories directly inside a directory, an empty path lists the mount point
+    pub fn read_dir(&self, directory: &str) -> Vec<PakDirEntry> {
+        let mut prefix = self.relative(directory);
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+
+        let depth = prefix.matches('/').count();
+
+        let mut entries = Vec::new();
+        let mut start = prefix.clone();
+        while let Some((key, name)) = self.names.range(start.clone()..).next() {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+
+            let mut components = name.split('/').skip(depth);
+            let entry_name = components.next().unwrap_or_default();
+            match components.next() {
+                Some(_) => {
+                    entries.push(PakDirEntry {
+                        name: entry_name.to_string(),
+                        path: name
+                            .split('/')
+                            .take(depth + 1)
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                        is_directory: true,
+                    });
+                    // skip the rest of the directory, '0' comes right after '/'
+                    let end = prefix.len() + key[prefix.len()..].find('/').unwrap_or_default();
+                    start = format!("{}0", &key[..end]);
+                }
+                None => {
+                    entries.push(PakDirEntry {
+                        name: entry_name.to_string(),
+                        path: name.clone(),
+                        is_directory: false,
+                    });
+                    start = key.clone() + "\0";
+                }
+            }
+        }
+        entries
+    }
+
+    /// Normalize a path and strip the mount point if it is included
+    fn relative(&self, path: &str) -> String {
+        let path = normalize_path(path);
+        match path.strip_prefix(&self.mount_point) {
+            Some(relative) if !self.mount_point.is_empty() => relative.to_string(),
+            _ => path,
+        }
+    }
+}
+
+/// Match a normalized path against the `/` separated segments of a normalized glob pattern
+pub(crate) fn glob_matches(pattern_segments: &[&str], path: &str) -> bool {
+    let segments: Vec<&str> = path.split('/').collect();
+    matches(
+        pattern_segments,
+        &segments,
+        |pattern| *pattern == "**",
+        |pattern, segment| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let segment: Vec<char> = segment.chars().collect();
+            matches(
+                &pattern,
+                &segment,
+                |pattern| *pattern == '*',
+                |pattern, char| *pattern == '?' || pattern == char,
+            )
+        },
+    )
+}
+
+/// Wildcard matching where `is_wildcard` elements of the pattern match any amount of items
+fn matches<P, T>(
+    pattern: &[P],
+    items: &[T],
+    is_wildcard: impl Fn(&P) -> bool,
+    is_match: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // last wildcard and the amount of items it matched so far
+    let mut backtrack = None;
+    while i < items.len() {
+        match pattern.get(p) {
+            Some(e) if is_wildcard(e) => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(e) if is_match(e, &items[i]) => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((wildcard, matched)) => {
+                    p = wildcard + 1;
+                    i = matched + 1;
+                    backtrack = Some((wildcard, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(is_wildcard)
+}
+
+impl<'data, R, W> PakFile<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    /// Build an index for case insensitive lookups, globs and directory listings
+    pub fn record_index(&self) -> RecordIndex {
+        RecordIndex::new(self)
+    }
+}
@@ -0,0 +1,226 @@
+use std::io::{ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha1::{Digest, Sha1};
+
+use crate::error::UnrealPakError;
+
+const SIGNATURE_MAGIC: u32 = 0x73832DAA;
+const SIGNATURE_VERSION: i32 = 1;
+
+/// Size of the chunks of a pak that are hashed for its .sig file
+pub const SIGNATURE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Private key used to sign .sig files
+///
+/// Components are little endian like the base64 values of the `SigningKey` in `Crypto.json`.
+#[derive(Debug, Clone)]
+pub struct PakSigningKey {
+    key: RsaPrivateKey,
+}
+
+impl PakSigningKey {
+    pub fn from_components(
+        modulus: &[u8],
+        public_exponent: &[u8],
+        private_exponent: &[u8],
+    ) -> Result<Self, UnrealPakError> {
+        let key = RsaPrivateKey::from_components(
+            BigUint::from_bytes_le(modulus),
+            BigUint::from_bytes_le(public_exponent),
+            BigUint::from_bytes_le(private_exponent),
+            Vec::new(),
+        )
+        .map_err(|_| UnrealPakError::invalid_signing_key())?;
+        Ok(PakSigningKey { key })
+    }
+
+    pub fn verifying_key(&self) -> PakVerifyingKey {
+        PakVerifyingKey {
+            key: self.key.to_public_key(),
+        }
+    }
+}
+
+impl From<RsaPrivateKey> for PakSigningKey {
+    fn from(key: RsaPrivateKey) -> Self {
+        PakSigningKey { key }
+    }
+}
+
+/// Public key used to verify .sig files, the key games ship with
+#[derive(Debug, Clone)]
+pub struct PakVerifyingKey {
+    key: RsaPublicKey,
+}
+
+impl PakVerifyingKey {
+    pub fn from_components(modulus: &[u8], exponent: &[u8]) -> Result<Self, UnrealPakError> {
+        let key = RsaPublicKey::new(
+            BigUint::from_bytes_le(modulus),
+            BigUint::from_bytes_le(exponent),
+        )
+        .map_err(|_| UnrealPakError::invalid_signing_key())?;
+        Ok(PakVerifyingKey { key })
+    }
+}
+
+impl From<RsaPublicKey> for PakVerifyingKey {
+    fn from(key: RsaPublicKey) -> Self {
+        PakVerifyingKey { key }
+    }
+}
+
+/// Contents of a .sig file, the crc32 of every 64KiB chunk of a pak and
+/// the rsa signed sha1 of those crcs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakSignature {
+    pub encrypted_hash: Vec<u8>,
+    pub chunk_hashes: Vec<u32>,
+}
+
+/// Result of `PakSignature::verify`
+#[derive(Debug, Clone, Default)]
+pub struct SignatureReport {
+    /// Whether the signature matches the chunk hashes of the .sig file
+    pub signature_valid: bool,
+    /// Number of chunks in the pak
+    pub chunk_count: usize,
+    /// Chunks that don't match the .sig file, including chunks missing from the pak or the .sig file
+    pub mismatched_chunks: Vec<usize>,
+}
+
+impl SignatureReport {
+    pub fn is_ok(&self) -> bool {
+        self.signature_valid && self.mismatched_chunks.is_empty()
+    }
+}
+
+impl PakSignature {
+    /// Hash the chunks of a pak and sign them
+    pub fn sign<R: Read>(reader: &mut R, key: &PakSigningKey) -> Result<Self, UnrealPakError> {
+        let chunk_hashes = hash_chunks(reader)?;
+        let encrypted_hash = key
+            .key
+            .sign(
+                Pkcs1v15Sign::new_unprefixed(),
+                &principal_hash(&chunk_hashes),
+            )
+            .map_err(|_| UnrealPakError::invalid_signing_key())?;
+        Ok(PakSignature {
+            encrypted_hash,
+            chunk_hashes,
+        })
+    }
+
+    /// Check the signature and the chunks of a pak against this .sig file
+    ///
+    /// Mismatches are collected in the report, an error is only returned if the pak can't be read.
+    pub fn verify<R: Read>(
+        &self,
+        reader: &mut R,
+        key: &PakVerifyingKey,
+    ) -> Result<SignatureReport, UnrealPakError> {
+        let chunk_hashes = hash_chunks(reader)?;
+        let mut report = SignatureReport {
+            signature_valid: key
+                .key
+                .verify(
+                    Pkcs1v15Sign::new_unprefixed(),
+                    &principal_hash(&self.chunk_hashes),
+                    &self.encrypted_hash,
+                )
+                .is_ok(),
+            chunk_count: chunk_hashes.len(),
+            mismatched_chunks: Vec::new(),
+        };
+
+        for i in 0..chunk_hashes.len().max(self.chunk_hashes.len()) {
+            if chunk_hashes.get(i) != self.chunk_hashes.get(i) {
+                report.mismatched_chunks.push(i);
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, UnrealPakError> {
+        if reader.read_u32::<LittleEndian>()? != SIGNATURE_MAGIC
+            || reader.read_i32::<LittleEndian>()? != SIGNATURE_VERSION
+        {
+            return Err(UnrealPakError::invalid_signature_file());
+        }
+
+        let hash_len = read_count(reader)?;
+        let mut encrypted_hash = Vec::new();
+        reader
+            .take(hash_len as u64)
+            .read_to_end(&mut encrypted_hash)?;
+        if encrypted_hash.len() != hash_len {
+            return Err(UnrealPakError::invalid_signature_file());
+        }
+
+        // the count is not trusted for allocating, the file has to actually contain the hashes
+        let chunk_count = read_count(reader)?;
+        let mut chunk_hashes = Vec::new();
+        for _ in 0..chunk_count {
+            chunk_hashes.push(reader.read_u32::<LittleEndian>()?);
+        }
+
+        Ok(PakSignature {
+            encrypted_hash,
+            chunk_hashes,
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), UnrealPakError> {
+        writer.write_u32::<LittleEndian>(SIGNATURE_MAGIC)?;
+        writer.write_i32::<LittleEndian>(SIGNATURE_VERSION)?;
+        writer.write_i32::<LittleEndian>(self.encrypted_hash.len() as i32)?;
+        writer.write_all(&self.encrypted_hash)?;
+        writer.write_i32::<LittleEndian>(self.chunk_hashes.len() as i32)?;
+        for hash in &self.chunk_hashes {
+            writer.write_u32::<LittleEndian>(*hash)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_count<R: Read>(reader: &mut R) -> Result<usize, UnrealPakError> {
+    usize::try_from(reader.read_i32::<LittleEndian>()?)
+        .map_err(|_| UnrealPakError::invalid_signature_file())
+}
+
+/// Crc32 of every chunk of the data, the last chunk may be shorter
+fn hash_chunks<R: Read>(reader: &mut R) -> Result<Vec<u32>, UnrealPakError> {
+    let mut hashes = Vec::new();
+    let mut chunk = vec![0u8; SIGNATURE_CHUNK_SIZE];
+    loop {
+        let mut len = 0;
+        while len < chunk.len() {
+            match reader.read(&mut chunk[len..]) {
+                Ok(0) => break,
+                Ok(read) => len += read,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if len == 0 {
+            break;
+        }
+        hashes.push(crc32fast::hash(&chunk[..len]));
+        if len < chunk.len() {
+            break;
+        }
+    }
+    Ok(hashes)
+}
+
+/// The hash that gets signed, sha1 over the chunk hashes
+fn principal_hash(chunk_hashes: &[u32]) -> Vec<u8> {
+    let mut hasher = Sha1::new();
+    for hash in chunk_hashes {
+        hasher.update(hash.to_le_bytes());
+    }
+    hasher.finalize().to_vec()
+}
@@ -0,0 +1,307 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::compression::{CompressionCodec, CompressionMethod, CompressionRegistry};
+use crate::encryption::{self, AesKey};
+use crate::error::UnrealPakError;
+use crate::layout::PakLayout;
+use crate::pakversion::PakVersion;
+use crate::PakRecord;
+
+/// Chunk size used when reading uncompressed records, must be a multiple of the aes block size
+const UNCOMPRESSED_CHUNK_SIZE: u64 = 0x10000;
+/// Largest compression block that is decompressed, every block is decompressed into a single allocation
+const MAX_BLOCK_SIZE: u64 = 0x4000000;
+/// Largest allocation made up front when reading a whole record or chunk, the rest grows as blocks are read
+pub(crate) const MAX_PREALLOCATION: u64 = 0x4000000;
+
+/// `Read + Seek` view over the decompressed data of a single record
+///
+/// Only the block that is currently being read is kept in memory,
+/// blocks are read, decrypted and decompressed when they are first accessed.
+pub struct PakRecordReader<'a, R>
+where
+    R: Read + Seek,
+{
+    reader: &'a mut R,
+    record: &'a PakRecord,
+    file_version: PakVersion,
+    /// Size of the record header in front of the data
+    header_size: u64,
+    encryption_key: Option<&'a AesKey>,
+    codec: Option<&'a dyn CompressionCodec>,
+    block_size: u64,
+    position: u64,
+    block: Option<(u64, Vec<u8>)>,
+}
+
+impl<'a, R> PakRecordReader<'a, R>
+where
+    R: Read + Seek,
+{
+    pub(crate) fn new(
+        reader: &'a mut R,
+        record: &'a PakRecord,
+        file_version: PakVersion,
+        layout: &PakLayout,
+        encryption_key: Option<&'a AesKey>,
+        compression: &'a CompressionRegistry,
+    ) -> Result<Self, UnrealPakError> {
+        let (codec, block_size) = match record.stored_compression_method() {
+            CompressionMethod::None => (None, UNCOMPRESSED_CHUNK_SIZE),
+            _ => {
+                if record.compression_blocks.is_none() {
+                    return Err(UnrealPakError::invalid_record());
+                }
+                let block_size = match record.compression_block_size {
+                    Some(block_size) if block_size > 0 => block_size as u64,
+                    _ => record.decompressed_size.max(1),
+                };
+                (
+                    Some(compression.get(record.stored_compression_method())?),
+                    block_size,
+                )
+            }
+        };
+
+        let header_size = record.header_size(file_version, layout);
+        validate(reader, record, file_version, header_size, block_size)?;
+
+        Ok(PakRecordReader {
+            reader,
+            record,
+            file_version,
+            header_size,
+            encryption_key,
+            codec,
+            block_size,
+            position: 0,
+            block: None,
+        })
+    }
+
+    /// Decompressed size of the record
+    pub fn len(&self) -> u64 {
+        self.record.decompressed_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.record.decompressed_size == 0
+    }
+
+    fn block_count(&self) -> u64 {
+        match self.record.compression_blocks {
+            Some(ref blocks) if self.codec.is_some() => blocks.len() as u64,
+            _ => self.record.decompressed_size.div_ceil(self.block_size),
+        }
+    }
+
+    /// Read `size` bytes at `offset`, decrypting them if the record is encrypted
+    fn read_raw(&mut self, offset: u64, size: u64) -> Result<Vec<u8>, UnrealPakError> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        // encrypted data is padded to the aes block size
+        let mut data = match self.encryption_key {
+            Some(_) => vec![0u8; encryption::align(size) as usize],
+            None => vec![0u8; size as usize],
+        };
+        self.reader.read_exact(&mut data)?;
+
+        if let Some(encryption_key) = self.encryption_key {
+            encryption_key.decrypt(&mut data);
+            data.truncate(size as usize);
+        }
+        Ok(data)
+    }
+
+    fn load_block(&mut self, index: u64) -> Result<(), UnrealPakError> {
+        if matches!(self.block, Some((cached, _)) if cached == index) {
+            return Ok(());
+        }
+
+        let block_start = index * self.block_size;
+        let decompressed_size = self
+            .block_size
+            .min(self.record.decompressed_size.saturating_sub(block_start));
+
+        let data = match self.codec {
+            None => {
+                let data_offset = self.record.offset + self.header_size;
+                self.read_raw(data_offset + block_start, decompressed_size)?
+            }
+            Some(codec) => {
+                let block = self
+                    .record
+                    .compression_blocks
+                    .as_ref()
+                    .and_then(|e| e.get(index as usize))
+                    .ok_or_else(UnrealPakError::invalid_record)?
+                    .clone();
+
+                let offset =
+                    match self.file_version >= PakVersion::PakFileVersionRelativeChunkOffsets {
+                        true => self.record.offset + block.start,
+                        false => block.start,
+                    };
+
+                let compressed_data = self.read_raw(offset, block.size)?;
+                let data = codec.decompress(&compressed_data, decompressed_size as usize)?;
+                if data.len() as u64 != decompressed_size {
+                    return Err(UnrealPakError::invalid_compression_block(
+                        self.record.offset,
+                    ));
+                }
+                data
+            }
+        };
+
+        self.block = Some((index, data));
+        Ok(())
+    }
+
+    /// Read the entire record into memory
+    pub(crate) fn read_all(mut self) -> Result<Vec<u8>, UnrealPakError> {
+        let mut data =
+            Vec::with_capacity(self.record.decompressed_size.min(MAX_PREALLOCATION) as usize);
+        for index in 0..self.block_count() {
+            self.load_block(index)?;
+            // the output never grows past the decompressed size of the record
+            let block = &self.block.as_ref().unwrap().1;
+            if data.len() as u64 + block.len() as u64 > self.record.decompressed_size {
+                return Err(UnrealPakError::invalid_compression_block(
+                    self.record.offset,
+                ));
+            }
+            data.extend_from_slice(block);
+        }
+        if data.len() as u64 != self.record.decompressed_size {
+            return Err(UnrealPakError::invalid_record_size(self.record.offset));
+        }
+        Ok(data)
+    }
+}
+
+/// Check the sizes and blocks of a record against each other and the length of the pak
+/// so that corrupt headers fail before anything is allocated for them
+fn validate<R>(
+    reader: &mut R,
+    record: &PakRecord,
+    file_version: PakVersion,
+    header_size: u64,
+    block_size: u64,
+) -> Result<(), UnrealPakError>
+where
+    R: Read + Seek,
+{
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    let data_start = record.offset.checked_add(header_size);
+    let data_end = data_start.and_then(|e| e.checked_add(record.stored_size()));
+    let (Some(data_start), Some(data_end)) = (data_start, data_end) else {
+        return Err(UnrealPakError::out_of_bounds(record.offset, header_size));
+    };
+    if data_end > file_size {
+        return Err(UnrealPakError::out_of_bounds(
+            record.offset,
+            data_end - record.offset,
+        ));
+    }
+
+    let blocks = match record.stored_compression_method() {
+        CompressionMethod::None => {
+            return match record.compressed_size == record.decompressed_size {
+                true => Ok(()),
+                false => Err(UnrealPakError::invalid_record_size(record.offset)),
+            };
+        }
+        _ => record.compression_blocks.as_deref().unwrap_or_default(),
+    };
+
+    if block_size > MAX_BLOCK_SIZE {
+        return Err(UnrealPakError::invalid_compression_block(record.offset));
+    }
+    if blocks.len() as u64 != record.decompressed_size.div_ceil(block_size) {
+        return Err(UnrealPakError::invalid_record_size(record.offset));
+    }
+
+    // blocks are stored in order without overlapping each other
+    let mut previous_end = data_start;
+    for block in blocks {
+        let start = match file_version >= PakVersion::PakFileVersionRelativeChunkOffsets {
+            true => record.offset.checked_add(block.start),
+            false => Some(block.start),
+        };
+        let size = match record.is_encrypted() {
+            true => encryption::align(block.size),
+            false => block.size,
+        };
+        match start {
+            Some(start)
+                if start >= data_start
+                    && start.checked_add(size).is_some_and(|e| e <= data_end) =>
+            {
+                if start < previous_end {
+                    return Err(UnrealPakError::invalid_compression_block(record.offset));
+                }
+                previous_end = start + size;
+            }
+            _ => {
+                return Err(UnrealPakError::out_of_bounds(
+                    start.unwrap_or(block.start),
+                    size,
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<'a, R> Read for PakRecordReader<'a, R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.record.decompressed_size {
+            return Ok(0);
+        }
+
+        let index = self.position / self.block_size;
+        self.load_block(index)?;
+
+        let block = &self.block.as_ref().unwrap().1;
+        let block_offset = (self.position - index * self.block_size) as usize;
+        if block_offset >= block.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "compression block is smaller than expected",
+            ));
+        }
+
+        let read = buf.len().min(block.len() - block_offset);
+        buf[..read].copy_from_slice(&block[block_offset..block_offset + read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a, R> Seek for PakRecordReader<'a, R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.record.decompressed_size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::error::UnrealPakError;
+use crate::layout::{PakLayout, RecordField};
+use crate::pakversion::PakVersion;
+use crate::{CompressionMethod, PakFile, PakRecord};
+
+/// Where the stored data of a pak that is opened for updating ends
+#[derive(Debug)]
+pub(crate) struct UpdateState<W> {
+    /// Offset of the index, new records are written from here, `None` until the records are loaded
+    pub data_end: Option<u64>,
+    /// Truncates the stream after the footer, so a pak can shrink when it is written
+    pub set_len: fn(&mut W, u64) -> io::Result<()>,
+}
+
+/// Streams that can be truncated, required to update a pak in place
+pub trait SetLen {
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+}
+
+impl SetLen for File {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+}
+
+impl SetLen for &File {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+}
+
+impl<W> SetLen for BufWriter<W>
+where
+    W: Write + SetLen,
+{
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().set_len(len)
+    }
+}
+
+impl SetLen for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.get_mut().resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+impl<T> SetLen for &mut T
+where
+    T: SetLen + ?Sized,
+{
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        (**self).set_len(len)
+    }
+}
+
+impl<'data> PakFile<'data> {
+    /// Open an existing pak file for updating, the file has to be opened for reading and writing
+    pub fn updater(file: &'data File) -> Self {
+        PakFile::from_updater(BufReader::new(file), BufWriter::new(file))
+    }
+}
+
+impl<'data, R, W> PakFile<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    /// Open an existing pak for updating, `reader` and `writer` have to refer to the same stream
+    ///
+    /// After `load_records`, `write` only writes records that were added since, replaces
+    /// the index and footer and truncates the stream after them. The data of replaced records
+    /// is left in place until `compact` is called.
+    pub fn from_updater(reader: R, writer: W) -> Self
+    where
+        W: SetLen,
+    {
+        let mut pak = PakFile::new(
+            PakVersion::PakFileVersionInvalid,
+            Some(reader),
+            Some(writer),
+        );
+        pak.update = Some(UpdateState {
+            data_end: None,
+            set_len: W::set_len,
+        });
+        pak
+    }
+
+    /// Move the stored records of a pak opened for updating next to each other to reclaim
+    /// the space of replaced records, then write a fresh index and footer and truncate the stream
+    ///
+    /// Records that were added since the last write are written as well.
+    pub fn compact(&mut self) -> Result<(), UnrealPakError> {
+        self.check_named_by_hash()?;
+        let update = self
+            .update
+            .as_mut()
+            .filter(|e| e.data_end.is_some())
+            .ok_or_else(UnrealPakError::invalid_pak_file)?;
+        let reader = self.reader.as_mut().unwrap();
+        let writer = self.writer.as_mut().unwrap();
+
+        let mut records = self
+            .records
+            .values_mut()
+            .filter(|e| e.stored && !e.is_deleted())
+            .collect::<Vec<_>>();
+        records.sort_by_key(|e| e.offset);
+
+        let mut position = 0;
+        let mut buf = vec![0u8; 0x10000];
+        for record in records {
+            // overlapping records would be overwritten while moving
+            if record.offset < position {
+                return Err(UnrealPakError::invalid_record());
+            }
+
+            let size = record.header_size(self.file_version, &self.layout) + record.stored_size();
+            if record.offset != position {
+                let mut moved = 0;
+                while moved < size {
+                    let chunk = &mut buf[..(size - moved).min(0x10000) as usize];
+                    reader.seek(SeekFrom::Start(record.offset + moved))?;
+                    reader.read_exact(chunk)?;
+                    writer.seek(SeekFrom::Start(position + moved))?;
+                    writer.write_all(chunk)?;
+                    writer.flush()?;
+                    moved += chunk.len() as u64;
+                }
+                move_header(
+                    reader,
+                    writer,
+                    record,
+                    position,
+                    self.file_version,
+                    &self.layout,
+                )?;
+            }
+            position += size;
+        }
+
+        update.data_end = Some(position);
+        self.write()
+    }
+}
+
+/// Fix up the header of a record whose data was moved to `offset`
+fn move_header<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    record: &mut PakRecord,
+    offset: u64,
+    file_version: PakVersion,
+    layout: &PakLayout,
+) -> Result<(), UnrealPakError>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let old_offset = record.offset;
+    record.offset = offset;
+
+    // the offset in front of the record is either empty or the record offset
+    if let Some(field_offset) =
+        layout.record_field_offset(RecordField::Offset, record, file_version)
+    {
+        reader.seek(SeekFrom::Start(offset + field_offset))?;
+        let mut stored_offset = [0u8; 8];
+        reader.read_exact(&mut stored_offset)?;
+        if u64::from_le_bytes(stored_offset) == old_offset {
+            writer.seek(SeekFrom::Start(offset + field_offset))?;
+            writer.write_u64::<LittleEndian>(offset)?;
+        }
+    }
+
+    // block offsets are absolute before relative chunk offsets
+    if file_version < PakVersion::PakFileVersionRelativeChunkOffsets
+        && *record.stored_compression_method() != CompressionMethod::None
+    {
+        let field_offset =
+            layout.record_field_offset(RecordField::CompressionBlocks, record, file_version);
+        if let (Some(field_offset), Some(ref mut blocks)) =
+            (field_offset, record.compression_blocks.as_mut())
+        {
+            // blocks follow the block count
+            writer.seek(SeekFrom::Start(offset + field_offset + 4))?;
+            for block in blocks.iter_mut() {
+                block.start = block.start - old_offset + offset;
+                writer.write_u64::<LittleEndian>(block.start)?;
+                writer.write_u64::<LittleEndian>(block.start + block.size)?;
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,379 @@
+use std::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use sha1::{Digest, Sha1};
+
+use crate::buf_ext::BufReaderExt;
+use crate::compression::CompressionRegistry;
+use crate::encryption::{self, AesKey};
+use crate::error::UnrealPakError;
+use crate::footer::PakFooter;
+use crate::index::SecondaryIndexInfo;
+use crate::layout::PakLayout;
+use crate::pakversion::PakVersion;
+use crate::stream::PakRecordReader;
+use crate::{CompressionMethod, PakFile, PakRecord};
+
+/// Result of `PakFile::verify`
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub records_checked: usize,
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A single integrity problem found in a pak file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyIssue {
+    /// Name of the record, `None` for problems with the index
+    pub record: Option<String>,
+    /// Offset of the record or index in the pak file
+    pub offset: u64,
+    pub kind: VerifyIssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyIssueKind {
+    /// Hash of the named index doesn't match the hash stored for it
+    IndexHashMismatch(String),
+    /// Record data extends into the index or past the end of the file
+    RecordOutOfBounds { end: u64 },
+    /// Compression block lies outside of the record data
+    BlockOutOfBounds { block: usize, start: u64, end: u64 },
+    /// Header in front of the record data doesn't match the index entry
+    HeaderMismatch,
+    /// Hash of the stored record data doesn't match the record header
+    HashMismatch { expected: Vec<u8>, actual: Vec<u8> },
+    /// Record data overlaps with the data of another record
+    Overlap { record: String, offset: u64 },
+    /// Record could not be read or decompressed
+    ReadFailed(String),
+}
+
+impl fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.record {
+            Some(ref record) => write!(f, "{} at offset {}: ", record, self.offset)?,
+            None => write!(f, "Index at offset {}: ", self.offset)?,
+        }
+
+        match self.kind {
+            VerifyIssueKind::IndexHashMismatch(ref index) => write!(f, "{} hash mismatch", index),
+            VerifyIssueKind::RecordOutOfBounds { end } => {
+                write!(f, "record data ends out of bounds at {}", end)
+            }
+            VerifyIssueKind::BlockOutOfBounds { block, start, end } => write!(
+                f,
+                "compression block {} at {}..{} is out of bounds",
+                block, start, end
+            ),
+            VerifyIssueKind::HeaderMismatch => write!(f, "record header doesn't match the index"),
+            VerifyIssueKind::HashMismatch {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "hash mismatch, expected {} got {}",
+                hex(expected),
+                hex(actual)
+            ),
+            VerifyIssueKind::Overlap { ref record, offset } => {
+                write!(f, "overlaps with {} at offset {}", record, offset)
+            }
+            VerifyIssueKind::ReadFailed(ref err) => write!(f, "read failed, {}", err),
+        }
+    }
+}
+
+pub(crate) fn hex(data: &[u8]) -> String {
+    data.iter().map(|e| format!("{:02x}", e)).collect()
+}
+
+impl<'data, R, W> PakFile<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    /// Check the integrity of a pak file
+    ///
+    /// The records are read from the index without failing on index hash mismatches, so they
+    /// can be reported like every other problem and `load_records` doesn't have to be called first.
+    /// Index hashes, record hashes, record and block bounds and overlapping records are checked
+    /// and every record is decompressed. Problems are collected in the report,
+    /// an error is only returned if the pak can't be read at all.
+    pub fn verify(&mut self) -> Result<VerifyReport, UnrealPakError> {
+        self.read_records(false)?;
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(UnrealPakError::invalid_pak_file)?;
+        let footer = PakFooter::read(reader, &self.layout)?;
+
+        let mut report = VerifyReport::default();
+        self.verify_index(&footer, &mut report.issues)?;
+
+        let compression_methods: Vec<_> = footer
+            .compression_methods
+            .iter()
+            .map(|e| CompressionMethod::from_name(e))
+            .collect();
+
+        let reader = self.reader.as_mut().unwrap();
+        let mut extents = Vec::with_capacity(self.records.len());
+        for record in self.records.values() {
+            report.records_checked += 1;
+            // delete records have no data
+            if record.is_deleted() {
+                continue;
+            }
+
+            let encryption_key = match record.is_encrypted() {
+                true => match self.encryption_keys.get(&self.encryption_key_guid) {
+                    Some(encryption_key) => Some(encryption_key),
+                    None => {
+                        let err = UnrealPakError::missing_encryption_key(self.encryption_key_guid);
+                        report.issues.push(VerifyIssue {
+                            record: Some(record.file_name.clone()),
+                            offset: record.offset,
+                            kind: VerifyIssueKind::ReadFailed(err.to_string()),
+                        });
+                        continue;
+                    }
+                },
+                false => None,
+            };
+
+            let end = verify_record(
+                reader,
+                record,
+                self.file_version,
+                &self.layout,
+                encryption_key,
+                &self.compression,
+                &compression_methods,
+                footer.index_offset,
+                &mut report.issues,
+            )?;
+            extents.push((record.offset, end, &record.file_name));
+        }
+
+        // every record is compared against the record reaching furthest so far,
+        // a large record can overlap many records after it
+        extents.sort();
+        let mut furthest: Option<(u64, u64, &String)> = None;
+        for (offset, end, name) in extents {
+            if let Some((other_offset, other_end, other_name)) = furthest {
+                if offset < other_end {
+                    report.issues.push(VerifyIssue {
+                        record: Some(name.clone()),
+                        offset,
+                        kind: VerifyIssueKind::Overlap {
+                            record: other_name.clone(),
+                            offset: other_offset,
+                        },
+                    });
+                }
+            }
+            if furthest.is_none_or(|(_, other_end, _)| end > other_end) {
+                furthest = Some((offset, end, name));
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn verify_index(
+        &mut self,
+        footer: &PakFooter,
+        issues: &mut Vec<VerifyIssue>,
+    ) -> Result<(), UnrealPakError> {
+        let index = self.read_raw_index_data(
+            footer.index_offset,
+            footer.index_size,
+            footer.is_index_encrypted,
+        )?;
+        if Sha1::digest(&index)[..] != footer.index_hash[..] {
+            issues.push(VerifyIssue {
+                record: None,
+                offset: footer.index_offset,
+                kind: VerifyIssueKind::IndexHashMismatch(String::from("primary index")),
+            });
+            // secondary index locations can't be trusted
+            return Ok(());
+        }
+
+        if self.file_version < PakVersion::PakFileVersionPathHashIndex {
+            return Ok(());
+        }
+
+        let mut reader = Cursor::new(index);
+        let _mount_point = reader.read_string()?;
+        let _record_count = reader.read_u32::<LittleEndian>()?;
+        let _path_hash_seed = reader.read_u64::<LittleEndian>()?;
+        let secondary_indices = [
+            ("path hash index", SecondaryIndexInfo::read(&mut reader)?),
+            (
+                "full directory index",
+                SecondaryIndexInfo::read(&mut reader)?,
+            ),
+        ];
+
+        for (name, info) in secondary_indices {
+            let Some(info) = info else {
+                continue;
+            };
+
+            let kind =
+                match self.read_raw_index_data(info.offset, info.size, footer.is_index_encrypted) {
+                    Ok(data) if Sha1::digest(&data)[..] == info.hash[..] => continue,
+                    Ok(_) => VerifyIssueKind::IndexHashMismatch(String::from(name)),
+                    Err(err) => VerifyIssueKind::ReadFailed(format!("{}, {}", name, err)),
+                };
+            issues.push(VerifyIssue {
+                record: None,
+                offset: info.offset,
+                kind,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Verify a single record, returns the end offset of the record data
+#[allow(clippy::too_many_arguments)]
+fn verify_record<R>(
+    reader: &mut R,
+    record: &PakRecord,
+    file_version: PakVersion,
+    layout: &PakLayout,
+    encryption_key: Option<&AesKey>,
+    compression: &CompressionRegistry,
+    compression_methods: &[CompressionMethod],
+    data_end: u64,
+    issues: &mut Vec<VerifyIssue>,
+) -> Result<u64, UnrealPakError>
+where
+    R: Read + Seek,
+{
+    let mut issue = |kind| {
+        issues.push(VerifyIssue {
+            record: Some(record.file_name.clone()),
+            offset: record.offset,
+            kind,
+        })
+    };
+
+    let stored_size = record.stored_size();
+    let data_start = record
+        .offset
+        .checked_add(record.header_size(file_version, layout));
+    let (Some(data_start), Some(end)) = (
+        data_start,
+        data_start.and_then(|e| e.checked_add(stored_size)),
+    ) else {
+        issue(VerifyIssueKind::RecordOutOfBounds { end: u64::MAX });
+        return Ok(u64::MAX);
+    };
+    if end > data_end {
+        issue(VerifyIssueKind::RecordOutOfBounds { end });
+        return Ok(end);
+    }
+
+    reader.seek(SeekFrom::Start(record.offset))?;
+    let header =
+        match PakRecord::read_header(reader, file_version, false, compression_methods, layout) {
+            Ok(header) => header,
+            Err(err) => {
+                issue(VerifyIssueKind::ReadFailed(err.to_string()));
+                return Ok(end);
+            }
+        };
+    // encoded records don't store a hash in the index
+    let has_index_hash = record.hash.iter().any(|e| *e != 0);
+    if header.compressed_size != record.compressed_size
+        || header.decompressed_size != record.decompressed_size
+        || header.compression_method != *record.stored_compression_method()
+        || (has_index_hash && header.hash != record.hash)
+    {
+        issue(VerifyIssueKind::HeaderMismatch);
+    }
+
+    if let Some(ref blocks) = record.compression_blocks {
+        for (i, block) in blocks.iter().enumerate() {
+            let start = match file_version >= PakVersion::PakFileVersionRelativeChunkOffsets {
+                true => record.offset.checked_add(block.start),
+                false => Some(block.start),
+            };
+            let size = match record.is_encrypted() {
+                true => encryption::align(block.size),
+                false => block.size,
+            };
+            let block_end = start.and_then(|e| e.checked_add(size));
+            match (start, block_end) {
+                (Some(start), Some(block_end)) if start >= data_start && block_end <= end => {}
+                _ => issue(VerifyIssueKind::BlockOutOfBounds {
+                    block: i,
+                    start: start.unwrap_or(u64::MAX),
+                    end: block_end.unwrap_or(u64::MAX),
+                }),
+            }
+        }
+    }
+
+    // the hash is usually taken over the decrypted data, some tools hash the encrypted data instead
+    let mut hasher = Sha1::new();
+    let mut encrypted_hasher = Sha1::new();
+    reader.seek(SeekFrom::Start(data_start))?;
+    let mut remaining = stored_size;
+    let mut hashed = 0;
+    let mut buf = vec![0u8; 0x10000];
+    while remaining > 0 {
+        let chunk = &mut buf[..remaining.min(0x10000) as usize];
+        reader.read_exact(chunk)?;
+        remaining -= chunk.len() as u64;
+
+        if let Some(encryption_key) = encryption_key {
+            encrypted_hasher.update(&chunk);
+            encryption_key.decrypt(chunk);
+        }
+        let size = (record.compressed_size - hashed).min(chunk.len() as u64);
+        hasher.update(&chunk[..size as usize]);
+        hashed += size;
+    }
+    let hash = hasher.finalize().to_vec();
+    if hash != header.hash
+        && (encryption_key.is_none() || encrypted_hasher.finalize()[..] != header.hash[..])
+    {
+        issue(VerifyIssueKind::HashMismatch {
+            expected: header.hash,
+            actual: hash,
+        });
+    }
+
+    let decompressed = PakRecordReader::new(
+        reader,
+        record,
+        file_version,
+        layout,
+        encryption_key,
+        compression,
+    )
+    .and_then(|mut e| Ok(io::copy(&mut e, &mut io::sink())?));
+    match decompressed {
+        Ok(size) if size == record.decompressed_size => {}
+        Ok(size) => issue(VerifyIssueKind::ReadFailed(format!(
+            "decompressed to {} bytes instead of {}",
+            size, record.decompressed_size
+        ))),
+        Err(err) => issue(VerifyIssueKind::ReadFailed(err.to_string())),
+    }
+
+    Ok(end)
+}
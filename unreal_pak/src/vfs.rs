@@ -0,0 +1,281 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+
+use crate::error::UnrealPakError;
+use crate::PakFile;
+
+/// A pak mounted into a `PakVfs`
+#[derive(Debug)]
+pub struct MountedPak<'data, R = BufReader<&'data File>, W = BufWriter<&'data File>>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    /// File name of the pak like `pakchunk0_1_P.pak`, used for priority rules
+    pub name: String,
+    /// Order including the patch priority, see `pak_order`
+    pub order: i32,
+    pub pak: PakFile<'data, R, W>,
+}
+
+/// A record providing a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsEntry {
+    /// Index of the pak in `PakVfs::paks`
+    pub pak: usize,
+    /// Name of the record in the pak, relative to its mount point
+    pub record_name: String,
+    /// Path including the mount point, without `../../../`
+    pub path: String,
+    /// Whether the record is a delete record
+    pub deleted: bool,
+}
+
+/// A file or directory in a `PakVfs` directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsDirEntry {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+}
+
+/// Layered view over many paks, resolving every path like the engine does
+///
+/// Paths include the mount point of their pak and are compared case insensitive,
+/// `../../../Game/Content/A.uasset`, `Game/Content/A.uasset` and `game\content\a.uasset`
+/// are the same path. A pak with a higher order wins, paks with the same order are
+/// ranked by file name with numeric prefixes like `100-` compared as numbers, the
+/// later name wins. A winning delete record hides the path.
+///
+/// Records are indexed when a pak is mounted, records added afterwards are not visible.
+#[derive(Debug)]
+pub struct PakVfs<'data, R = BufReader<&'data File>, W = BufWriter<&'data File>>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    paks: Vec<MountedPak<'data, R, W>>,
+    /// Providers of every normalized path, the winner first
+    entries: BTreeMap<String, Vec<VfsEntry>>,
+}
+
+impl<'data, R, W> Default for PakVfs<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    fn default() -> Self {
+        PakVfs {
+            paks: Vec::new(),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'data, R, W> PakVfs<'data, R, W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    pub fn new() -> Self {
+        PakVfs::default()
+    }
+
+    /// Mount a pak with loaded records, returns its index
+    ///
+    /// `base_order` is the order of the location the pak was found in, the engine uses
+    /// 4 for the project content paks. Patch priority is added from the file name.
+    pub fn mount(&mut self, name: &str, base_order: i32, pak: PakFile<'data, R, W>) -> usize {
+        let index = self.paks.len();
+        let mount_point = String::from_utf8_lossy(&pak.mount_point).replace('\\', "/");
+
+        let mut touched = Vec::new();
+        // records named by hash have no path to mount them at
+        for record in pak.records.values().filter(|e| !e.is_named_by_hash()) {
+            let path = clean_path(&join_path(&mount_point, &record.file_name));
+            let key = path.to_lowercase();
+            self.entries.entry(key.clone()).or_default().push(VfsEntry {
+                pak: index,
+                record_name: record.file_name.clone(),
+                path,
+                deleted: record.is_deleted(),
+            });
+            touched.push(key);
+        }
+
+        self.paks.push(MountedPak {
+            name: name.to_string(),
+            order: pak_order(name, base_order),
+            pak,
+        });
+
+        let paks = &self.paks;
+        for key in touched {
+            if let Some(entries) = self.entries.get_mut(&key) {
+                entries.sort_by(|a, b| compare_paks(&paks[b.pak], &paks[a.pak]));
+            }
+        }
+        index
+    }
+
+    pub fn paks(&self) -> &[MountedPak<'data, R, W>] {
+        &self.paks
+    }
+
+    pub fn pak_mut(&mut self, index: usize) -> Option<&mut MountedPak<'data, R, W>> {
+        self.paks.get_mut(index)
+    }
+
+    /// Every record providing a path, the winner first
+    pub fn providers(&self, path: &str) -> &[VfsEntry] {
+        self.entries
+            .get(&normalize_path(path))
+            .map(|e| e.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The record a path resolves to, `None` if no pak has it or it was deleted
+    pub fn resolve(&self, path: &str) -> Option<&VfsEntry> {
+        self.providers(path).first().filter(|e| !e.deleted)
+    }
+
+    /// The winning record if it hides the record of `pak` for a path
+    pub fn shadowed_by(&self, path: &str, pak: usize) -> Option<&VfsEntry> {
+        let providers = self.providers(path);
+        match providers.iter().any(|e| e.pak == pak) {
+            true => providers.first().filter(|e| e.pak != pak),
+            false => None,
+        }
+    }
+
+    /// Every path that resolves to a record, sorted case insensitive
+    pub fn files(&self) -> impl Iterator<Item = &VfsEntry> {
+        self.entries
+            .values()
+            .filter_map(|e| e.first())
+            .filter(|e| !e.deleted)
+    }
+
+    /// Files and directories directly inside a directory, an empty path lists the root
+    pub fn read_dir(&self, directory: &str) -> Vec<VfsDirEntry> {
+        let mut prefix = normalize_path(directory);
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        let depth = prefix.matches('/').count();
+
+        let mut children: BTreeMap<String, VfsDirEntry> = BTreeMap::new();
+        for (key, entries) in self.entries.range(prefix.clone()..) {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            let Some(entry) = entries.first().filter(|e| !e.deleted) else {
+                continue;
+            };
+
+            let mut components = entry.path.split('/').skip(depth);
+            let Some(name) = components.next() else {
+                continue;
+            };
+            children
+                .entry(name.to_lowercase())
+                .or_insert_with(|| VfsDirEntry {
+                    name: name.to_string(),
+                    path: entry
+                        .path
+                        .split('/')
+                        .take(depth + 1)
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    is_directory: components.next().is_some(),
+                });
+        }
+        children.into_values().collect()
+    }
+
+    /// Read the data of the record a path resolves to
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>, UnrealPakError> {
+        let entry = self
+            .resolve(path)
+            .ok_or_else(|| UnrealPakError::record_not_found(path.to_string()))?
+            .clone();
+        let record = self.paks[entry.pak].pak.get_record(&entry.record_name)?;
+        Ok(record.data.clone().unwrap_or_default())
+    }
+}
+
+/// Order of a pak the way the engine computes it from the file name
+///
+/// Patch paks ending in `_P` get 100 added, numbered patches like `pakchunk0_2_P.pak`
+/// get 100 times the patch number plus one so later patches win.
+pub fn pak_order(name: &str, base_order: i32) -> i32 {
+    let stem = file_stem(name);
+    // the suffix is compared case insensitive like the rest of the name
+    let Some(stripped) = stem.strip_suffix("_P").or_else(|| stem.strip_suffix("_p")) else {
+        return base_order;
+    };
+
+    let version = stripped
+        .rsplit_once('_')
+        .and_then(|(_, version)| version.parse::<i32>().ok())
+        .filter(|e| *e >= 1)
+        .map(|e| e + 1)
+        .unwrap_or(1);
+    base_order.saturating_add(version.saturating_mul(100))
+}
+
+/// Compare the priority of two mounted paks, the greater one wins
+fn compare_paks<R, W>(a: &MountedPak<'_, R, W>, b: &MountedPak<'_, R, W>) -> Ordering
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    a.order.cmp(&b.order).then_with(|| {
+        let a_name = file_stem(&a.name).to_lowercase();
+        let b_name = file_stem(&b.name).to_lowercase();
+        match (numeric_prefix(&a_name), numeric_prefix(&b_name)) {
+            (Some(a_prefix), Some(b_prefix)) => a_prefix.cmp(&b_prefix),
+            _ => Ordering::Equal,
+        }
+        .then_with(|| a_name.cmp(&b_name))
+    })
+}
+
+fn file_stem(name: &str) -> &str {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    match name.len() > 4 && name[name.len() - 4..].eq_ignore_ascii_case(".pak") {
+        true => &name[..name.len() - 4],
+        false => name,
+    }
+}
+
+fn numeric_prefix(name: &str) -> Option<u64> {
+    let end = name
+        .find(|e: char| !e.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..end].parse().ok()
+}
+
+/// Path of a record with the mount point in front of it
+pub(crate) fn join_path(mount_point: &str, record_name: &str) -> String {
+    match mount_point.is_empty() || mount_point.ends_with('/') {
+        true => format!("{}{}", mount_point, record_name),
+        false => format!("{}/{}", mount_point, record_name),
+    }
+}
+
+/// Strip mount point prefixes like `../../../` and use forward slashes
+pub(crate) fn clean_path(path: &str) -> String {
+    let mut path = path.replace('\\', "/");
+    while let Some(stripped) = path.strip_prefix("../").or_else(|| path.strip_prefix('/')) {
+        path = stripped.to_string();
+    }
+    path
+}
+
+/// Paths are compared case insensitive and without mount point prefixes
+pub(crate) fn normalize_path(path: &str) -> String {
+    clean_path(path).to_lowercase()
+}
@@ -0,0 +1,110 @@
+use unreal_pak::chunk::PakChunker;
+
+#[test]
+fn asset_files_stay_together() {
+    let mut chunker = PakChunker::new();
+    chunker.add_rule("Game/Content/Fonts/*.uasset", 2);
+    chunker.add_rule("Game/Content/Textures/T.uasset", 3);
+
+    let plan = chunker.assign([
+        ("Game/Content/Maps/Map.umap", 10),
+        ("Game/Content/Maps/Map.uexp", 10),
+        ("Game/Content/Fonts/Font.uasset", 10),
+        ("Game/Content/Fonts/Font.ufont", 10),
+        ("Game/Content/Textures/T.uasset", 10),
+        ("Game/Content/Textures/T.uexp", 10),
+        ("Game/Content/Textures/T.ubulk", 10),
+        ("Game/Content/Textures/T.m.ubulk", 10),
+        ("Game/Content/Textures/T.uptnl", 10),
+    ]);
+
+    let chunks: Vec<(String, usize)> = plan
+        .chunks
+        .iter()
+        .map(|e| (e.name(), e.files.len()))
+        .collect();
+    assert_eq!(
+        chunks,
+        [
+            (String::from("pakchunk0"), 2),
+            (String::from("pakchunk2"), 2),
+            (String::from("pakchunk3"), 5),
+        ]
+    );
+}
+
+#[test]
+fn chunks_are_split_by_size() {
+    let mut chunker = PakChunker::new();
+    chunker.max_size = Some(100);
+    chunker.default_chunk = 1;
+    chunker.add_rule("Game/Content/Maps/**", 4);
+    // the first matching rule wins
+    chunker.add_rule("Game/Content/**", 5);
+
+    let plan = chunker.assign([
+        ("Game/Content/A.uasset", 40),
+        ("Game/Content/A.uexp", 20),
+        ("Game/Content/B.uasset", 30),
+        // larger than max_size, gets a pak of its own
+        ("Game/Content/C.uasset", 150),
+        ("Game/Content/C.ubulk", 50),
+        ("Game/Content/D.uasset", 60),
+        ("Game/Content/Maps/Map.umap", 30),
+        ("Game/Config/Game.ini", 10),
+    ]);
+
+    let chunks: Vec<(String, u64, Vec<&str>)> = plan
+        .chunks
+        .iter()
+        .map(|e| {
+            let files = e.files.iter().map(|e| e.as_str()).collect();
+            (e.name(), e.size, files)
+        })
+        .collect();
+    assert_eq!(
+        chunks,
+        [
+            (String::from("pakchunk1"), 10, vec!["Game/Config/Game.ini"]),
+            (
+                String::from("pakchunk4"),
+                30,
+                vec!["Game/Content/Maps/Map.umap"]
+            ),
+            (
+                String::from("pakchunk5"),
+                90,
+                vec![
+                    "Game/Content/A.uasset",
+                    "Game/Content/A.uexp",
+                    "Game/Content/B.uasset"
+                ]
+            ),
+            (
+                String::from("pakchunk5_s1"),
+                200,
+                vec!["Game/Content/C.uasset", "Game/Content/C.ubulk"]
+            ),
+            (
+                String::from("pakchunk5_s2"),
+                60,
+                vec!["Game/Content/D.uasset"]
+            ),
+        ]
+    );
+    assert_eq!(plan.chunks[4].part, 2);
+    assert_eq!(chunker.chunk_id("game/content/maps/other.umap"), 4);
+    assert_eq!(chunker.chunk_id("Engine/Content/E.uasset"), 1);
+
+    assert_eq!(
+        plan.manifest(),
+        "pakchunk1 Game/Config/Game.ini\n\
+         pakchunk4 Game/Content/Maps/Map.umap\n\
+         pakchunk5 Game/Content/A.uasset\n\
+         pakchunk5 Game/Content/A.uexp\n\
+         pakchunk5 Game/Content/B.uasset\n\
+         pakchunk5_s1 Game/Content/C.uasset\n\
+         pakchunk5_s1 Game/Content/C.ubulk\n\
+         pakchunk5_s2 Game/Content/D.uasset\n"
+    );
+}
@@ -0,0 +1,120 @@
+use std::io::{self, Cursor, Read};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use unreal_pak::compression::{CompressionCodec, CompressionRegistry};
+use unreal_pak::error::UnrealPakErrorKind;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+/// Run length codec, stands in for a game specific method
+struct RleCodec;
+
+impl CompressionCodec for RleCodec {
+    fn compress(&self, data: &[u8], _level: Option<i32>) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let len = data[i..]
+                .iter()
+                .take(u8::MAX as usize)
+                .take_while(|e| **e == data[i])
+                .count();
+            compressed.extend_from_slice(&[len as u8, data[i]]);
+            i += len;
+        }
+        Ok(compressed)
+    }
+
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(decompressed_size);
+        for run in data.chunks_exact(2) {
+            decompressed.resize(decompressed.len() + run[0] as usize, run[1]);
+        }
+        Ok(decompressed)
+    }
+}
+
+fn rle() -> CompressionMethod {
+    CompressionMethod::Custom("Rle".to_string())
+}
+
+#[test]
+fn builtin_codecs_use_their_formats() {
+    let data = b"compression block ".repeat(100);
+    let registry = CompressionRegistry::default();
+
+    for level in [None, Some(1), Some(9)] {
+        let codec = registry.get(&CompressionMethod::Zlib).unwrap();
+        let compressed = codec.compress(&data, level).unwrap();
+        let mut decompressed = Vec::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        let codec = registry.get(&CompressionMethod::Gzip).unwrap();
+        let compressed = codec.compress(&data, level).unwrap();
+        let mut decompressed = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        let codec = registry.get(&CompressionMethod::Zstd).unwrap();
+        let compressed = codec.compress(&data, level).unwrap();
+        assert_eq!(
+            zstd::bulk::decompress(&compressed, data.len()).unwrap(),
+            data
+        );
+
+        let codec = registry.get(&CompressionMethod::Lz4).unwrap();
+        let compressed = codec.compress(&data, level).unwrap();
+        assert_eq!(
+            lz4_flex::block::decompress(&compressed, data.len()).unwrap(),
+            data
+        );
+    }
+
+    for compression_method in [CompressionMethod::Oodle, rle()] {
+        let Err(err) = registry.get(&compression_method) else {
+            panic!("{compression_method:?} has a built-in codec");
+        };
+        assert!(matches!(
+            err.kind(),
+            UnrealPakErrorKind::UnsupportedCompression(e) if *e == compression_method
+        ));
+    }
+}
+
+#[test]
+fn custom_codec() {
+    let name = String::from("Game/custom.uasset");
+    let data = [[1u8; 1000], [2u8; 1000]].concat().repeat(50);
+
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.block_size = 0x4000;
+    pak.register_compression_codec(rle(), RleCodec);
+    pak.add_record(PakRecord::new(name.clone(), data.clone(), rle()).unwrap())
+        .unwrap();
+    pak.write().unwrap();
+    drop(pak);
+    let output = output.into_inner();
+
+    // the method name is stored in the footer
+    assert!(output.windows(3).any(|e| e == b"Rle"));
+
+    let mut pak = PakFile::from_reader(Cursor::new(output.clone()));
+    pak.load_records().unwrap();
+    assert_eq!(pak.records[&name].compression_method, rle());
+    assert!(matches!(
+        pak.get_record(&name).unwrap_err().kind(),
+        UnrealPakErrorKind::UnsupportedCompression(e) if *e == rle()
+    ));
+
+    let mut pak = PakFile::from_reader(Cursor::new(output));
+    pak.register_compression_codec(rle(), RleCodec);
+    pak.load_records().unwrap();
+    assert_eq!(pak.get_record(&name).unwrap().data.as_ref(), Some(&data));
+    assert!(pak.verify().unwrap().is_ok());
+}
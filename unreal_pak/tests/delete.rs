@@ -0,0 +1,132 @@
+use std::io::Cursor;
+
+use unreal_pak::error::{UnrealPakError, UnrealPakErrorKind};
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::vfs::PakVfs;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord, PakRecordKind};
+
+fn write(
+    file_version: PakVersion,
+    files: &[(&str, Option<&[u8]>)],
+) -> Result<Vec<u8>, UnrealPakError> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(file_version, &mut output);
+    for (name, data) in files {
+        let record = match data {
+            Some(data) => {
+                PakRecord::new(name.to_string(), data.to_vec(), CompressionMethod::Zlib).unwrap()
+            }
+            None => PakRecord::new_deleted(name.to_string()),
+        };
+        pak.add_record(record).unwrap();
+    }
+    pak.write()?;
+    drop(pak);
+    Ok(output.into_inner())
+}
+
+fn load(data: Vec<u8>) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    pak
+}
+
+#[test]
+fn delete_records_round_trip() {
+    let files: [(&str, Option<&[u8]>); 2] = [
+        ("Game/Content/Kept.uasset", Some(b"kept")),
+        ("Game/Content/Removed.uasset", None),
+    ];
+    for file_version in [
+        PakVersion::PakFileVersionDeleteRecords,
+        PakVersion::PakFileVersionEncryptionKeyGuid,
+        PakVersion::PakFileVersionFnameBasedCompressionMethod,
+        PakVersion::PakFileVersionPathHashIndex,
+        PakVersion::PakFileVersionFnv64bugFix,
+    ] {
+        let mut pak = load(write(file_version, &files).unwrap());
+        assert!(pak.verify().unwrap().is_ok(), "{file_version:?}");
+
+        let kept = pak.get_record(&files[0].0.to_string()).unwrap();
+        assert_eq!(kept.kind(), PakRecordKind::File);
+        assert_eq!(kept.data.as_deref(), Some(b"kept".as_slice()));
+
+        let removed = pak.get_record(&files[1].0.to_string()).unwrap();
+        assert_eq!(removed.kind(), PakRecordKind::Deleted, "{file_version:?}");
+        assert!(removed.data.is_none());
+        assert_eq!(removed.decompressed_size, 0);
+    }
+}
+
+#[test]
+fn delete_records_need_version_6() {
+    let err = write(
+        PakVersion::PakFileVersionRelativeChunkOffsets,
+        &[("Game/Content/Removed.uasset", None)],
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        UnrealPakErrorKind::UnsupportedPakVersion(PakVersion::PakFileVersionRelativeChunkOffsets)
+    ));
+}
+
+#[test]
+fn deleted_paths_are_absent() {
+    let version = PakVersion::PakFileVersionFnv64bugFix;
+    let mut vfs = PakVfs::new();
+    vfs.mount(
+        "pakchunk0-Windows.pak",
+        0,
+        load(
+            write(
+                version,
+                &[
+                    ("Game/Content/A.uasset", Some(b"a")),
+                    ("Game/Content/Maps/B.umap", Some(b"b")),
+                    ("Game/Content/C.uasset", Some(b"c")),
+                ],
+            )
+            .unwrap(),
+        ),
+    );
+    let patch = vfs.mount(
+        "pakchunk0-Windows_0_P.pak",
+        0,
+        load(
+            write(
+                version,
+                &[
+                    ("Game/Content/A.uasset", None),
+                    ("Game/Content/Maps/B.umap", None),
+                    ("Game/Content/C.uasset", None),
+                ],
+            )
+            .unwrap(),
+        ),
+    );
+    let restore = vfs.mount(
+        "pakchunk0-Windows_1_P.pak",
+        0,
+        load(write(version, &[("Game/Content/C.uasset", Some(b"new c"))]).unwrap()),
+    );
+
+    // a delete record hides the path from lookups and listings
+    assert!(vfs.resolve("Game/Content/A.uasset").is_none());
+    assert_eq!(vfs.providers("Game/Content/A.uasset")[0].pak, patch);
+    assert!(vfs.providers("Game/Content/A.uasset")[0].deleted);
+    assert!(vfs.read_dir("Game/Content/Maps").is_empty());
+
+    // a higher priority pak can add the file again
+    assert_eq!(vfs.resolve("Game/Content/C.uasset").unwrap().pak, restore);
+    assert_eq!(vfs.read("Game/Content/C.uasset").unwrap(), b"new c");
+
+    let files: Vec<&str> = vfs.files().map(|e| e.path.as_str()).collect();
+    assert_eq!(files, ["Game/Content/C.uasset"]);
+    let names: Vec<String> = vfs
+        .read_dir("Game/Content")
+        .into_iter()
+        .map(|e| e.name)
+        .collect();
+    assert_eq!(names, ["C.uasset"]);
+}
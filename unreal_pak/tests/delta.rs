@@ -0,0 +1,177 @@
+use std::io::Cursor;
+
+use unreal_pak::delta::{apply_patch, create_patch, ManifestEntry, PatchManifest};
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+fn write(file_version: PakVersion, files: &[(&str, Vec<u8>, CompressionMethod)]) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(file_version, &mut output);
+    pak.mount_point = b"../../../Game/Content/".to_vec();
+    for (name, data, compression_method) in files {
+        let record =
+            PakRecord::new(name.to_string(), data.clone(), compression_method.clone()).unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn load(data: Vec<u8>) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    pak
+}
+
+#[test]
+fn create_and_apply_patch() {
+    let big: Vec<u8> = (0..200_000u32).map(|e| (e % 253) as u8).collect();
+    let mut changed = big.clone();
+    changed[100] = 0;
+
+    for file_version in [
+        PakVersion::PakFileVersionDeleteRecords,
+        PakVersion::PakFileVersionFnameBasedCompressionMethod,
+        PakVersion::PakFileVersionFnv64bugFix,
+    ] {
+        let old = write(
+            file_version,
+            &[
+                ("same", big.clone(), CompressionMethod::Zlib),
+                ("changed", big.clone(), CompressionMethod::Zlib),
+                ("removed", vec![1; 10], CompressionMethod::None),
+                ("raw", vec![2; 10], CompressionMethod::None),
+            ],
+        );
+        let new = write(
+            file_version,
+            &[
+                ("same", big.clone(), CompressionMethod::Zlib),
+                ("changed", changed.clone(), CompressionMethod::Zlib),
+                ("added", vec![3; 50], CompressionMethod::None),
+                ("raw", vec![2; 10], CompressionMethod::None),
+            ],
+        );
+
+        let mut patch_output = Cursor::new(Vec::new());
+        let mut patch = PakFile::from_writer(file_version, &mut patch_output);
+        let (delta, manifest) =
+            create_patch(&mut load(old.clone()), &mut load(new.clone()), &mut patch).unwrap();
+        drop(patch);
+        assert_eq!(delta.added, ["added"]);
+        assert_eq!(delta.changed, ["changed"]);
+        assert_eq!(delta.removed, ["removed"]);
+        assert_eq!(delta.unchanged, 2);
+
+        let mut patch = load(patch_output.into_inner());
+        let names: Vec<&str> = patch.records.keys().map(|e| e.as_str()).collect();
+        assert_eq!(names, ["added", "changed", "removed"]);
+        assert!(patch.records["removed"].is_deleted());
+
+        // the manifest is stored separately from the patch
+        let mut manifest_output = Vec::new();
+        manifest.write(&mut manifest_output).unwrap();
+        let manifest = PatchManifest::read(manifest_output.as_slice()).unwrap();
+        let kinds: Vec<_> = manifest
+            .records
+            .iter()
+            .map(|(name, entry)| {
+                (
+                    name.as_str(),
+                    matches!(entry, ManifestEntry::Patched { .. }),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("added", true),
+                ("changed", true),
+                ("raw", false),
+                ("same", false)
+            ]
+        );
+
+        // the rebuilt pak is byte identical to the new pak
+        let mut target_output = Cursor::new(Vec::new());
+        let mut target = PakFile::from_writer(file_version, &mut target_output);
+        let applied =
+            apply_patch(&mut load(old.clone()), &mut patch, &manifest, &mut target).unwrap();
+        drop(target);
+        assert_eq!(applied.added, delta.added);
+        assert_eq!(applied.changed, delta.changed);
+        assert_eq!(applied.removed, delta.removed);
+        assert_eq!(applied.unchanged, delta.unchanged);
+        assert!(target_output.into_inner() == new);
+
+        // a patch only applies to the pak it was created from
+        let wrong = write(
+            file_version,
+            &[
+                ("same", vec![9; 5], CompressionMethod::Zlib),
+                ("raw", vec![2; 10], CompressionMethod::None),
+            ],
+        );
+        let mut target = PakFile::from_writer(file_version, Cursor::new(Vec::new()));
+        assert!(apply_patch(&mut load(wrong), &mut patch, &manifest, &mut target).is_err());
+
+        // records missing from the patch aren't taken from the old pak
+        let mut target = PakFile::from_writer(file_version, Cursor::new(Vec::new()));
+        assert!(apply_patch(
+            &mut load(old.clone()),
+            &mut load(old),
+            &manifest,
+            &mut target
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn recompressed_records_are_unchanged() {
+    let data: Vec<u8> = (0..100_000u32).map(|e| (e % 7) as u8).collect();
+    let file_version = PakVersion::PakFileVersionFnameBasedCompressionMethod;
+    let old = write(
+        file_version,
+        &[
+            ("zlib", data.clone(), CompressionMethod::Zlib),
+            ("raw", data.clone(), CompressionMethod::None),
+        ],
+    );
+    let new = write(
+        file_version,
+        &[
+            ("zlib", data.clone(), CompressionMethod::Zstd),
+            ("raw", data.clone(), CompressionMethod::Zlib),
+        ],
+    );
+
+    let mut patch_output = Cursor::new(Vec::new());
+    let mut patch = PakFile::from_writer(file_version, &mut patch_output);
+    let (delta, manifest) =
+        create_patch(&mut load(old.clone()), &mut load(new), &mut patch).unwrap();
+    drop(patch);
+    assert!(delta.changed.is_empty());
+    assert_eq!(delta.unchanged, 2);
+    assert!(load(patch_output.get_ref().clone()).records.is_empty());
+
+    // unchanged records are rebuilt from the old pak
+    let mut target_output = Cursor::new(Vec::new());
+    let mut target = PakFile::from_writer(file_version, &mut target_output);
+    let mut patch = load(patch_output.into_inner());
+    apply_patch(&mut load(old), &mut patch, &manifest, &mut target).unwrap();
+    drop(target);
+    let mut target = load(target_output.into_inner());
+    for name in ["zlib", "raw"] {
+        assert_eq!(
+            target
+                .get_record(&name.to_string())
+                .unwrap()
+                .data
+                .as_ref()
+                .unwrap(),
+            &data
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::Cursor;
+
+use unreal_pak::encryption::AesKey;
+use unreal_pak::error::UnrealPakErrorKind;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const KEY: [u8; 32] = [0x5a; 32];
+const GUID: [u8; 16] = [0x11; 16];
+const MOUNT_POINT: &[u8] = b"../../../EncryptedGame/Content/";
+
+fn write(file_version: PakVersion, encryption_key_guid: [u8; 16]) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(file_version, &mut output);
+    pak.mount_point = MOUNT_POINT.to_vec();
+    pak.encryption_key_guid = encryption_key_guid;
+    pak.encrypt_index = true;
+    pak.add_encryption_key(encryption_key_guid, AesKey::new(KEY));
+    for name in ["Maps/Secret.umap", "Data/Table.uasset"] {
+        let data = name.repeat(100).into_bytes();
+        pak.add_record(PakRecord::new(name.to_string(), data, CompressionMethod::Zlib).unwrap())
+            .unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn load(data: &[u8], key: Option<[u8; 32]>) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut pak = PakFile::from_reader(Cursor::new(data.to_vec()));
+    if let Some(key) = key {
+        pak.add_encryption_key(GUID, AesKey::new(key));
+        pak.add_encryption_key([0u8; 16], AesKey::new(key));
+    }
+    pak
+}
+
+#[test]
+fn encrypted_index_round_trip() {
+    for (file_version, guid) in [
+        (PakVersion::PakFileVersionIndexEncryption, [0u8; 16]),
+        (PakVersion::PakFileVersionFnameBasedCompressionMethod, GUID),
+        (PakVersion::PakFileVersionFnv64bugFix, GUID),
+    ] {
+        let data = write(file_version, guid);
+
+        // neither the mount point nor the record names are stored in plain text
+        for text in [MOUNT_POINT, b"Maps/Secret.umap"] {
+            assert!(
+                !data.windows(text.len()).any(|e| e == text),
+                "{file_version:?}"
+            );
+        }
+
+        let mut pak = load(&data, None);
+        assert!(matches!(
+            pak.load_records().unwrap_err().kind(),
+            UnrealPakErrorKind::MissingEncryptionKey(e) if *e == guid
+        ));
+
+        // a wrong key decrypts to data that doesn't match the index hash
+        let mut pak = load(&data, Some([0x5b; 32]));
+        assert!(matches!(
+            pak.load_records().unwrap_err().kind(),
+            UnrealPakErrorKind::IndexHashMismatch
+        ));
+
+        let mut pak = load(&data, Some(KEY));
+        pak.load_records().unwrap();
+        assert!(pak.encrypt_index);
+        assert_eq!(pak.encryption_key_guid, guid);
+        assert_eq!(pak.mount_point, MOUNT_POINT);
+        assert!(pak.verify().unwrap().is_ok());
+        for name in ["Maps/Secret.umap", "Data/Table.uasset"] {
+            let record = pak.get_record(&name.to_string()).unwrap();
+            assert!(!record.is_encrypted());
+            assert_eq!(record.data.as_ref(), Some(&name.repeat(100).into_bytes()));
+        }
+    }
+}
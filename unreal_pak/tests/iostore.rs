@@ -0,0 +1,148 @@
+use std::io::{Cursor, Write};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use unreal_pak::iostore::{IoChunkId, IoChunkType, IoStoreReader, IoStoreTocVersion};
+
+const BLOCK_SIZE: usize = 0x10000;
+const INVALID_INDEX: u32 = u32::MAX;
+
+fn write_string(writer: &mut Vec<u8>, string: &str) {
+    writer.extend_from_slice(&(string.len() as i32 + 1).to_le_bytes());
+    writer.extend_from_slice(string.as_bytes());
+    writer.push(0);
+}
+
+fn write_u32s(writer: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        writer.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn chunk_id(id: u64) -> IoChunkId {
+    IoChunkId::new(id, 0, IoChunkType::ExportBundleData)
+}
+
+/// Build a version 2 container with an uncompressed map spanning three blocks
+/// and a zlib compressed table, both in the directory index
+fn write_container() -> (Vec<u8>, Vec<u8>) {
+    let map = vec![7u8; 3 * BLOCK_SIZE];
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"table").unwrap();
+    let table = encoder.finish().unwrap();
+
+    // blocks as (offset, compressed size, uncompressed size, method)
+    let mut cas = Vec::new();
+    let mut blocks = Vec::new();
+    for block in map.chunks(BLOCK_SIZE) {
+        blocks.push((
+            cas.len() as u64,
+            block.len() as u32,
+            block.len() as u32,
+            0u8,
+        ));
+        cas.extend_from_slice(block);
+    }
+    blocks.push((cas.len() as u64, table.len() as u32, 5, 1));
+    cas.extend_from_slice(&table);
+
+    let mut directory_index = Vec::new();
+    write_string(&mut directory_index, "../../../");
+    // root, Game, Game/Maps and Game/Data
+    write_u32s(&mut directory_index, &[4]);
+    write_u32s(
+        &mut directory_index,
+        &[INVALID_INDEX, 1, INVALID_INDEX, INVALID_INDEX],
+    );
+    write_u32s(&mut directory_index, &[0, 2, INVALID_INDEX, INVALID_INDEX]);
+    write_u32s(&mut directory_index, &[1, INVALID_INDEX, 3, 0]);
+    write_u32s(&mut directory_index, &[2, INVALID_INDEX, INVALID_INDEX, 1]);
+    write_u32s(&mut directory_index, &[2]);
+    write_u32s(&mut directory_index, &[3, INVALID_INDEX, 0]);
+    write_u32s(&mut directory_index, &[4, INVALID_INDEX, 1]);
+    write_u32s(&mut directory_index, &[5]);
+    for name in ["Game", "Maps", "Data", "Map.umap", "Table.uasset"] {
+        write_string(&mut directory_index, name);
+    }
+
+    let mut toc = Vec::new();
+    toc.extend_from_slice(b"-==--==--==--==-");
+    toc.extend_from_slice(&[IoStoreTocVersion::DirectoryIndex.into(), 0, 0, 0]);
+    write_u32s(
+        &mut toc,
+        &[
+            144,
+            2,
+            blocks.len() as u32,
+            12,
+            1,
+            32,
+            BLOCK_SIZE as u32,
+            directory_index.len() as u32,
+            0,
+        ],
+    );
+    toc.extend_from_slice(&0x1234u64.to_le_bytes());
+    toc.extend_from_slice(&[0u8; 16]);
+    // indexed and compressed
+    toc.extend_from_slice(&[0x09, 0, 0, 0]);
+    toc.resize(144, 0);
+
+    toc.extend_from_slice(&chunk_id(1).to_bytes());
+    toc.extend_from_slice(&chunk_id(2).to_bytes());
+    for (offset, length) in [(0u64, map.len() as u64), (map.len() as u64, 5)] {
+        toc.extend_from_slice(&offset.to_be_bytes()[3..]);
+        toc.extend_from_slice(&length.to_be_bytes()[3..]);
+    }
+    for (offset, compressed_size, uncompressed_size, method) in blocks {
+        toc.extend_from_slice(&offset.to_le_bytes()[..5]);
+        toc.extend_from_slice(&compressed_size.to_le_bytes()[..3]);
+        toc.extend_from_slice(&uncompressed_size.to_le_bytes()[..3]);
+        toc.push(method);
+    }
+    let mut name = [0u8; 32];
+    name[..4].copy_from_slice(b"Zlib");
+    toc.extend_from_slice(&name);
+    toc.extend_from_slice(&directory_index);
+    for _ in 0..2 {
+        toc.extend_from_slice(&[0u8; 33]);
+    }
+
+    (toc, cas)
+}
+
+#[test]
+fn hostile_counts_fail_without_allocating() {
+    let (toc, cas) = write_container();
+
+    // entry count, compression block count, compression method name count and directory index size
+    for offset in [24, 28, 36, 48] {
+        let mut toc = toc.clone();
+        toc[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut reader =
+            IoStoreReader::from_readers(Cursor::new(toc), vec![Cursor::new(cas.clone())]);
+        assert!(reader.load().is_err(), "count at {offset} was accepted");
+    }
+}
+
+#[test]
+fn read_container() {
+    let (toc, cas) = write_container();
+
+    let mut reader = IoStoreReader::from_readers(Cursor::new(toc), vec![Cursor::new(cas)]);
+    reader.load().unwrap();
+    assert_eq!(reader.version, IoStoreTocVersion::DirectoryIndex);
+    assert_eq!(reader.container_id, 0x1234);
+    assert_eq!(reader.mount_point, "../../../");
+    assert_eq!(reader.files().len(), 2);
+    assert_eq!(
+        reader.read_file("Game/Maps/Map.umap").unwrap(),
+        vec![7u8; 3 * BLOCK_SIZE]
+    );
+    assert_eq!(
+        reader.read_file("Game/Data/Table.uasset").unwrap(),
+        b"table"
+    );
+    assert_eq!(reader.read_chunk_by_id(&chunk_id(2)).unwrap(), b"table");
+}
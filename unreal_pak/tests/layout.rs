@@ -0,0 +1,80 @@
+use std::io::Cursor;
+
+use unreal_pak::layout::{FooterField, PakLayout, RecordField};
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+fn write(file_version: PakVersion, layout: PakLayout) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(file_version, &mut output);
+    pak.layout = layout;
+    for (name, compression_method) in [
+        ("Game/a.uasset", CompressionMethod::Zlib),
+        ("Game/b.uexp", CompressionMethod::None),
+    ] {
+        let data = name.repeat(1000).into_bytes();
+        pak.add_record(PakRecord::new(name.to_string(), data, compression_method).unwrap())
+            .unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn check(pak: &mut PakFile<'_, Cursor<Vec<u8>>>) {
+    assert!(pak.verify().unwrap().is_ok());
+    for name in ["Game/a.uasset", "Game/b.uexp"] {
+        let record = pak.get_record(&name.to_string()).unwrap();
+        assert_eq!(record.data.as_ref(), Some(&name.repeat(1000).into_bytes()));
+    }
+}
+
+#[test]
+fn ue4_22_footer_is_detected() {
+    let version = PakVersion::PakFileVersionFnameBasedCompressionMethod;
+    let data = write(version, PakLayout::ue4_22());
+    assert_eq!(
+        PakLayout::default().footer_size(version) - PakLayout::ue4_22().footer_size(version),
+        32
+    );
+
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    assert_eq!(pak.layout, PakLayout::ue4_22());
+    check(&mut pak);
+
+    // stock paks keep the stock layout
+    let mut pak = PakFile::from_reader(Cursor::new(write(version, PakLayout::default())));
+    pak.load_records().unwrap();
+    assert_eq!(pak.layout, PakLayout::default());
+    check(&mut pak);
+}
+
+#[test]
+fn custom_layout_round_trip() {
+    let mut layout = PakLayout {
+        magic: 0x12345678,
+        encode_records: false,
+        ..PakLayout::default()
+    };
+    layout.footer_fields.insert(2, FooterField::Padding(8));
+    layout.footer_fields.swap(4, 5);
+    layout.record_fields.insert(3, RecordField::Padding(4));
+
+    for version in [
+        PakVersion::PakFileVersionFnameBasedCompressionMethod,
+        PakVersion::PakFileVersionFnv64bugFix,
+    ] {
+        let data = write(version, layout.clone());
+
+        // the magic doesn't match the stock layout
+        let mut pak = PakFile::from_reader(Cursor::new(data.clone()));
+        assert!(pak.load_records().is_err());
+
+        let mut pak = PakFile::from_reader(Cursor::new(data));
+        pak.layout = layout.clone();
+        pak.load_records().unwrap();
+        assert_eq!(pak.layout, layout);
+        check(&mut pak);
+    }
+}
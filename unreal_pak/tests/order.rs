@@ -0,0 +1,74 @@
+use std::io::Cursor;
+
+use unreal_pak::order::PakOrder;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+/// Order file as written by the cooker, paths include the mount point
+const ORDER_FILE: &str = r#""../../../Game/Content/Maps/Map.umap" 1
+"../../../Game/Content/Maps/Map.uexp" 2
+"../../../Game/Content/Data/Big Table.uasset" 3
+"../../../Game/Content/Data/Big Table.uexp" 4
+"#;
+
+/// Write a pak with the order file and return its record names in the order they are stored
+fn stored_order(mount_point: &str, names: &[&str]) -> Vec<String> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.mount_point = mount_point.as_bytes().to_vec();
+    pak.order = Some(PakOrder::parse(ORDER_FILE).unwrap());
+    for name in names {
+        let record = PakRecord::new(
+            name.to_string(),
+            name.as_bytes().to_vec(),
+            CompressionMethod::None,
+        )
+        .unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+
+    let mut pak = PakFile::from_reader(Cursor::new(output.into_inner()));
+    pak.load_records().unwrap();
+    let mut records: Vec<_> = pak.records.values().collect();
+    records.sort_by_key(|e| e.offset);
+    records.iter().map(|e| e.file_name.clone()).collect()
+}
+
+#[test]
+fn order_file_paths_include_the_mount_point() {
+    // unlisted records are written last, ordered by name
+    let expected = [
+        "Maps/Map.umap",
+        "Maps/Map.uexp",
+        "Data/Big Table.uasset",
+        "Data/Big Table.uexp",
+        "Data/Extra.uasset",
+        "Maps/Other.umap",
+    ];
+    let mut names = expected.to_vec();
+    names.sort();
+    assert_eq!(stored_order("../../../Game/Content/", &names), expected);
+
+    // the same records with the mount point at the root
+    let names: Vec<String> = names.iter().map(|e| format!("Game/Content/{e}")).collect();
+    let names: Vec<&str> = names.iter().map(|e| e.as_str()).collect();
+    let expected: Vec<String> = expected
+        .iter()
+        .map(|e| format!("Game/Content/{e}"))
+        .collect();
+    assert_eq!(stored_order("../../../", &names), expected);
+}
+
+#[test]
+fn priorities() {
+    let order =
+        PakOrder::parse("Game/a.uasset\n\"Game/b c.uasset\" 0\n../../../Game/d 7\n").unwrap();
+    assert_eq!(order.priority("../../../", "Game/a.uasset"), Some(0));
+    assert_eq!(order.priority("../../../Game/", "B C.uasset"), Some(0));
+    assert_eq!(order.priority("../../../Game", "d"), Some(7));
+    assert_eq!(order.priority("../../../", "Game/missing"), None);
+    assert!(PakOrder::parse("\"unterminated 1").is_err());
+    assert!(PakOrder::parse("Game/a.uasset first").is_err());
+}
@@ -0,0 +1,72 @@
+use std::io::Cursor;
+
+use rayon::ThreadPoolBuilder;
+use unreal_pak::encryption::AesKey;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+/// Write records of several blocks each with every built-in method
+fn write(compression_level: Option<i32>) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.block_size = 0x1000;
+    pak.compression_level = compression_level;
+    pak.encrypt_records = true;
+    pak.add_encryption_key([0u8; 16], AesKey::new([0x21; 32]));
+
+    let methods = [
+        CompressionMethod::None,
+        CompressionMethod::Zlib,
+        CompressionMethod::Gzip,
+        CompressionMethod::Zstd,
+        CompressionMethod::Lz4,
+    ];
+    for i in 0..40 {
+        let data: Vec<u8> = (0..0x1000 * (i % 7 + 1) + i * 13)
+            .map(|e| (e * (i + 1) / 5 % 97) as u8)
+            .collect();
+        let record = PakRecord::new(
+            format!("Game/Content/{i:02}.uasset"),
+            data,
+            methods[i % methods.len()].clone(),
+        )
+        .unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn write_with_threads(num_threads: usize, compression_level: Option<i32>) -> Vec<u8> {
+    ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap()
+        .install(|| write(compression_level))
+}
+
+#[test]
+fn parallel_output_matches_single_threaded() {
+    for compression_level in [None, Some(1), Some(9)] {
+        let single = write_with_threads(1, compression_level);
+        for num_threads in [2, 8] {
+            assert!(
+                write_with_threads(num_threads, compression_level) == single,
+                "{num_threads} threads at level {compression_level:?}"
+            );
+        }
+
+        let mut pak = PakFile::from_reader(Cursor::new(single));
+        pak.add_encryption_key([0u8; 16], AesKey::new([0x21; 32]));
+        pak.load_records().unwrap();
+        assert_eq!(pak.records.len(), 40);
+        assert!(pak.verify().unwrap().is_ok());
+    }
+}
+
+#[test]
+fn compression_level_is_used() {
+    assert!(write(Some(1)) != write(Some(9)));
+    assert!(write(None) == write(None));
+}
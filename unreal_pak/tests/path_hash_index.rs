@@ -0,0 +1,180 @@
+use std::io::Cursor;
+
+use sha1::{Digest, Sha1};
+use unreal_pak::error::UnrealPakErrorKind;
+use unreal_pak::layout::PakLayout;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::vfs::PakVfs;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Write a version 11 pak and mark its full directory index as absent
+fn write_without_directory_index(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut data);
+    for (name, contents) in files {
+        let record =
+            PakRecord::new(name.to_string(), contents.to_vec(), CompressionMethod::Zlib).unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    let mut data = data.into_inner();
+
+    let footer_size = PakLayout::default().footer_size(PakVersion::PakFileVersionFnv64bugFix);
+    let footer = data.len() - footer_size as usize;
+    let index_offset = read_u64(&data, footer + 25) as usize;
+    let index_size = read_u64(&data, footer + 33) as usize;
+
+    // mount point, record count, path hash seed and path hash index info
+    let mount_point_len =
+        i32::from_le_bytes(data[index_offset..index_offset + 4].try_into().unwrap());
+    let full_directory_index = index_offset + 4 + mount_point_len as usize + 4 + 8 + 40 + 4;
+    data[full_directory_index..full_directory_index + 8].copy_from_slice(&(-1i64).to_le_bytes());
+
+    let hash = Sha1::digest(&data[index_offset..index_offset + index_size]);
+    data[footer + 41..footer + 61].copy_from_slice(&hash);
+    data
+}
+
+#[test]
+fn load_without_full_directory_index() {
+    let files: [(&str, &[u8]); 2] = [
+        ("Game/Content/a.uasset", b"first record"),
+        ("Game/Content/b.uexp", b"second record"),
+    ];
+    let data = write_without_directory_index(&files);
+
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    assert_eq!(pak.records.len(), files.len());
+
+    let names: Vec<String> = pak.records.keys().cloned().collect();
+    let mut contents = Vec::new();
+    for name in names {
+        assert!(pak.records[&name].is_named_by_hash());
+        assert_eq!(name.len(), 16);
+        assert!(u64::from_str_radix(&name, 16).is_ok());
+        contents.push(pak.get_record(&name).unwrap().data.clone().unwrap());
+    }
+    contents.sort();
+    assert_eq!(
+        contents,
+        vec![b"first record".to_vec(), b"second record".to_vec()]
+    );
+}
+
+#[test]
+fn records_named_by_hash_are_not_rewritten() {
+    let data = write_without_directory_index(&[("Game/Content/a.uasset", b"record")]);
+
+    // the hex names would end up in the new index as if they were paths
+    let mut pak = PakFile::from_updater(Cursor::new(data.clone()), Cursor::new(data.clone()));
+    pak.load_records().unwrap();
+    pak.add_record(PakRecord::new("b".to_string(), vec![1], CompressionMethod::None).unwrap())
+        .unwrap();
+    assert!(matches!(
+        pak.write().unwrap_err().kind(),
+        UnrealPakErrorKind::NamedByHash(_)
+    ));
+
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    let mut vfs = PakVfs::new();
+    vfs.mount("pakchunk0-Windows.pak", 4, pak);
+    assert_eq!(vfs.files().count(), 0);
+}
+
+#[test]
+fn frozen_index_is_rejected() {
+    let version = PakVersion::PakFileVersionFrozenIndex;
+    let mut data = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(version, &mut data);
+    pak.add_record(PakRecord::new("a".to_string(), vec![1], CompressionMethod::None).unwrap())
+        .unwrap();
+    pak.write().unwrap();
+    let mut data = data.into_inner();
+
+    // the frozen flag follows the index hash
+    let footer = data.len() - PakLayout::default().footer_size(version) as usize;
+    data[footer + 61] = 1;
+
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    let err = pak.load_records().unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        UnrealPakErrorKind::FrozenIndexUnsupported
+    ));
+}
+
+/// Read the path hashes stored in the path hash index of an unencrypted pak
+fn read_path_hashes(data: &[u8], file_version: PakVersion) -> Vec<u64> {
+    let footer_size = PakLayout::default().footer_size(file_version);
+    let footer = data.len() - footer_size as usize;
+    let index_offset = read_u64(data, footer + 25) as usize;
+
+    let mount_point_len =
+        i32::from_le_bytes(data[index_offset..index_offset + 4].try_into().unwrap());
+    let path_hash_index_info = index_offset + 4 + mount_point_len as usize + 4 + 8 + 4;
+    let path_hash_index = read_u64(data, path_hash_index_info) as usize;
+
+    let count = i32::from_le_bytes(
+        data[path_hash_index..path_hash_index + 4]
+            .try_into()
+            .unwrap(),
+    );
+    (0..count as usize)
+        .map(|i| read_u64(data, path_hash_index + 4 + i * 12))
+        .collect()
+}
+
+#[test]
+fn path_hashes_match_the_engine() {
+    // fnv64 of the lowercase utf-16 path with the seed added to the offset basis,
+    // version 10 has the offset basis and prime swapped
+    for (file_version, seed, expected) in [
+        (PakVersion::PakFileVersionFnv64bugFix, 0, 0x906a453f214eb8d9),
+        (
+            PakVersion::PakFileVersionFnv64bugFix,
+            0x1234abcd,
+            0xe7d8f2187ae9016e,
+        ),
+        (
+            PakVersion::PakFileVersionPathHashIndex,
+            0,
+            0x18a16cc87424fd2f,
+        ),
+        (
+            PakVersion::PakFileVersionPathHashIndex,
+            0x1234abcd,
+            0x94061775bf3798fc,
+        ),
+    ] {
+        let mut data = Cursor::new(Vec::new());
+        let mut pak = PakFile::from_writer(file_version, &mut data);
+        pak.path_hash_seed = seed;
+        let record = PakRecord::new(
+            "Game/Content/Maps/MAP.umap".to_string(),
+            b"map".to_vec(),
+            CompressionMethod::None,
+        )
+        .unwrap();
+        pak.add_record(record).unwrap();
+        pak.write().unwrap();
+        drop(pak);
+
+        let data = data.into_inner();
+        assert_eq!(
+            read_path_hashes(&data, file_version),
+            [expected],
+            "{file_version:?} with seed {seed:#x}"
+        );
+
+        let mut pak = PakFile::from_reader(Cursor::new(data));
+        pak.load_records().unwrap();
+        assert_eq!(pak.path_hash_seed, seed);
+        assert!(pak.verify().unwrap().is_ok());
+    }
+}
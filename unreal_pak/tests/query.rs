@@ -0,0 +1,141 @@
+use std::io::Cursor;
+
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::query::{PakDirEntry, RecordIndex};
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const NAMES: [&str; 7] = [
+    "Content/A.uasset",
+    "Content/Maps/Map.umap",
+    "Content/Maps/Map.uexp",
+    "Content/Maps.txt",
+    "Content/Maps/Sub/Deep.uasset",
+    "Content/UI/Icon.uasset",
+    "Config/DefaultGame.ini",
+];
+
+fn index(mount_point: &str, names: &[&str]) -> RecordIndex {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.mount_point = mount_point.as_bytes().to_vec();
+    for name in names {
+        let record = PakRecord::new(name.to_string(), Vec::new(), CompressionMethod::None).unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.record_index()
+}
+
+fn entry(name: &str, path: &str, is_directory: bool) -> PakDirEntry {
+    PakDirEntry {
+        name: name.to_string(),
+        path: path.to_string(),
+        is_directory,
+    }
+}
+
+#[test]
+fn find() {
+    let index = index("../../../Game/", &NAMES);
+    assert_eq!(index.len(), NAMES.len());
+
+    assert_eq!(index.find("Content/A.uasset"), Some("Content/A.uasset"));
+    assert_eq!(index.find("content/a.UASSET"), Some("Content/A.uasset"));
+    assert_eq!(
+        index.find("Content\\Maps\\Map.umap"),
+        Some("Content/Maps/Map.umap")
+    );
+    // paths including the mount point
+    assert_eq!(
+        index.find("../../../Game/Content/UI/Icon.uasset"),
+        Some("Content/UI/Icon.uasset")
+    );
+    assert_eq!(
+        index.find("game/config/defaultgame.ini"),
+        Some("Config/DefaultGame.ini")
+    );
+    assert_eq!(index.find("Content/Maps"), None);
+    assert_eq!(index.find("Content/B.uasset"), None);
+}
+
+#[test]
+fn glob() {
+    let index = index("../../../Game/", &NAMES);
+
+    // `**` matches no directories as well as many
+    assert_eq!(
+        index.glob("Content/**/*.uasset"),
+        [
+            "Content/A.uasset",
+            "Content/Maps/Sub/Deep.uasset",
+            "Content/UI/Icon.uasset"
+        ]
+    );
+    assert_eq!(index.glob("**/*.uasset").len(), 3);
+    assert_eq!(
+        index.glob("../../../Game/content/maps/**"),
+        [
+            "Content/Maps/Map.uexp",
+            "Content/Maps/Map.umap",
+            "Content/Maps/Sub/Deep.uasset"
+        ]
+    );
+
+    // `*` and `?` stay within a directory
+    assert_eq!(index.glob("Content/*.uasset"), ["Content/A.uasset"]);
+    assert_eq!(
+        index.glob("Content/Maps/Map.u???"),
+        ["Content/Maps/Map.uexp", "Content/Maps/Map.umap"]
+    );
+    assert_eq!(index.glob("Content/Maps*"), ["Content/Maps.txt"]);
+    assert_eq!(index.glob("Content/*/*.umap"), ["Content/Maps/Map.umap"]);
+    assert!(index.glob("Content/*.umap").is_empty());
+    assert_eq!(index.glob("*/*/*/*"), ["Content/Maps/Sub/Deep.uasset"]);
+}
+
+#[test]
+fn read_dir() {
+    let index = index("../../../Game/", &NAMES);
+
+    assert_eq!(
+        index.read_dir(""),
+        [
+            entry("Config", "Config", true),
+            entry("Content", "Content", true)
+        ]
+    );
+    // directories are listed once, in the order of their paths next to files sharing their prefix
+    assert_eq!(
+        index.read_dir("../../../Game/Content/"),
+        [
+            entry("A.uasset", "Content/A.uasset", false),
+            entry("Maps.txt", "Content/Maps.txt", false),
+            entry("Maps", "Content/Maps", true),
+            entry("UI", "Content/UI", true),
+        ]
+    );
+    assert_eq!(
+        index.read_dir("content\\maps"),
+        [
+            entry("Map.uexp", "Content/Maps/Map.uexp", false),
+            entry("Map.umap", "Content/Maps/Map.umap", false),
+            entry("Sub", "Content/Maps/Sub", true),
+        ]
+    );
+    assert!(index.read_dir("Content/Missing").is_empty());
+}
+
+#[test]
+fn many_records() {
+    let names: Vec<String> = (0..100_000)
+        .map(|i| format!("Content/{:02}/{:03}/Asset{i}.uasset", i % 97, i % 1009))
+        .collect();
+    let names: Vec<&str> = names.iter().map(|e| e.as_str()).collect();
+    let index = index("../../../Game/", &names);
+
+    assert_eq!(index.read_dir("Content").len(), 97);
+    for i in (0..100_000).step_by(997) {
+        assert_eq!(index.find(&names[i].to_lowercase()), Some(names[i]));
+        let directory = &names[i][..names[i].rfind('/').unwrap()];
+        assert!(!index.glob(&format!("{directory}/*.uasset")).is_empty());
+    }
+}
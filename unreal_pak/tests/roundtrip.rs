@@ -0,0 +1,236 @@
+use std::io::Cursor;
+
+use unreal_pak::encryption::AesKey;
+use unreal_pak::error::UnrealPakError;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const KEY: [u8; 32] = [0x42; 32];
+
+/// Compression methods a version can store
+fn compression_methods(file_version: PakVersion) -> Vec<CompressionMethod> {
+    if file_version < PakVersion::PakFileVersionCompressionEncryption {
+        vec![CompressionMethod::None]
+    } else if file_version < PakVersion::PakFileVersionFnameBasedCompressionMethod {
+        vec![
+            CompressionMethod::None,
+            CompressionMethod::Zlib,
+            CompressionMethod::Gzip,
+        ]
+    } else {
+        vec![
+            CompressionMethod::None,
+            CompressionMethod::Zlib,
+            CompressionMethod::Gzip,
+            CompressionMethod::Zstd,
+            CompressionMethod::Lz4,
+        ]
+    }
+}
+
+/// Record and index encryption combinations a version can store
+fn encryption_modes(file_version: PakVersion) -> Vec<(bool, bool)> {
+    let mut modes = vec![(false, false)];
+    if file_version >= PakVersion::PakFileVersionCompressionEncryption {
+        modes.push((true, false));
+    }
+    if file_version >= PakVersion::PakFileVersionIndexEncryption {
+        modes.push((false, true));
+        modes.push((true, true));
+    }
+    modes
+}
+
+/// Bytes that don't get smaller when compressed
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545f4914f6cdd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn incompressible_record_keeps_compression_method_until_reloaded() {
+    let name = String::from("Game/noise.bin");
+    let data = noise(0x20000);
+
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.add_record(PakRecord::new(name.clone(), data.clone(), CompressionMethod::Zlib).unwrap())
+        .unwrap();
+    pak.write().unwrap();
+    assert_eq!(
+        pak.records[&name].compression_method,
+        CompressionMethod::Zlib
+    );
+    drop(pak);
+
+    let mut pak = PakFile::from_reader(Cursor::new(output.into_inner()));
+    pak.load_records().unwrap();
+    assert!(pak.verify().unwrap().is_ok());
+    // only the method the data is stored with is in the pak
+    let record = pak.get_record(&name).unwrap();
+    assert_eq!(record.compression_method, CompressionMethod::None);
+    assert_eq!(record.data.as_ref(), Some(&data));
+}
+
+#[test]
+fn write_read_verify() {
+    let big: Vec<u8> = (0..300_000u32)
+        .map(|e| (e % 251) as u8 ^ (e / 1000) as u8)
+        .collect();
+    let files = [
+        ("Game/Content/big.uasset", big),
+        ("Game/Content/small.uexp", b"small record".to_vec()),
+        ("Game/Content/empty.bin", Vec::new()),
+        ("Game/noise.bin", noise(0x1000)),
+    ];
+
+    for file_version in 1..PakVersion::PakFileVersionLast as i32 {
+        let file_version = PakVersion::try_from(file_version).unwrap();
+        for compression_method in compression_methods(file_version) {
+            for (encrypt_records, encrypt_index) in encryption_modes(file_version) {
+                let case = format!(
+                    "{:?} {:?} records {} index {}",
+                    file_version, compression_method, encrypt_records, encrypt_index
+                );
+
+                let mut output = Cursor::new(Vec::new());
+                let mut pak = PakFile::from_writer(file_version, &mut output);
+                pak.mount_point = b"../../../".to_vec();
+                pak.encrypt_records = encrypt_records;
+                pak.encrypt_index = encrypt_index;
+                pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+                for (name, data) in &files {
+                    let record =
+                        PakRecord::new(name.to_string(), data.clone(), compression_method.clone())
+                            .unwrap();
+                    pak.add_record(record).unwrap();
+                }
+                if file_version >= PakVersion::PakFileVersionDeleteRecords {
+                    pak.add_record(PakRecord::new_deleted("Game/deleted.uasset".to_string()))
+                        .unwrap();
+                }
+                pak.write().unwrap();
+                drop(pak);
+
+                let mut pak = PakFile::from_reader(Cursor::new(output.into_inner()));
+                pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+                pak.load_records().unwrap();
+                assert_eq!(pak.file_version, file_version, "{case}");
+                assert_eq!(pak.mount_point, b"../../../", "{case}");
+                assert_eq!(pak.encrypt_index, encrypt_index, "{case}");
+
+                let report = pak.verify().unwrap();
+                assert!(report.is_ok(), "{case}: {:?}", report.issues);
+
+                for (name, data) in &files {
+                    let record = pak.get_record(&name.to_string()).unwrap();
+                    assert_eq!(record.is_encrypted(), encrypt_records, "{case} {name}");
+                    assert_eq!(record.data.as_ref(), Some(data), "{case} {name}");
+                }
+                let deleted = pak.records.get("Game/deleted.uasset");
+                assert_eq!(
+                    deleted.is_some_and(|e| e.is_deleted()),
+                    file_version >= PakVersion::PakFileVersionDeleteRecords,
+                    "{case}"
+                );
+            }
+        }
+    }
+}
+
+/// Write a single record with the given settings
+fn write_with(
+    file_version: PakVersion,
+    encrypt_records: bool,
+    encrypt_index: bool,
+    encryption_key_guid: [u8; 16],
+) -> Result<(), UnrealPakError> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(file_version, &mut output);
+    pak.encrypt_records = encrypt_records;
+    pak.encrypt_index = encrypt_index;
+    pak.encryption_key_guid = encryption_key_guid;
+    pak.add_encryption_key(encryption_key_guid, AesKey::new(KEY));
+    let record = PakRecord::new("a.txt".to_string(), b"a".to_vec(), CompressionMethod::None)?;
+    pak.add_record(record)?;
+    pak.write()
+}
+
+#[test]
+fn unsupported_settings_are_rejected() {
+    let zero = [0u8; 16];
+    let guid = [1u8; 16];
+    assert!(write_with(PakVersion::PakFileVersionNoTimestamps, true, false, zero).is_err());
+    assert!(write_with(
+        PakVersion::PakFileVersionCompressionEncryption,
+        false,
+        true,
+        zero
+    )
+    .is_err());
+    // older versions can't store the key guid and are always read with the zero guid
+    assert!(write_with(PakVersion::PakFileVersionDeleteRecords, true, true, guid).is_err());
+    assert!(write_with(
+        PakVersion::PakFileVersionEncryptionKeyGuid,
+        true,
+        true,
+        guid
+    )
+    .is_ok());
+}
+
+/// Every record of a pak with its data
+fn read_all(data: Vec<u8>) -> (PakVersion, Vec<(String, Vec<u8>)>) {
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    let report = pak.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.issues);
+
+    let names: Vec<String> = pak.records.keys().cloned().collect();
+    let records = names
+        .into_iter()
+        .map(|name| {
+            let data = pak.get_record(&name).unwrap().data.clone().unwrap();
+            (name, data)
+        })
+        .collect();
+    (pak.file_version, records)
+}
+
+#[test]
+fn testfiles() {
+    for mount_point in ["cus", "off"] {
+        let read = |compression: &str| {
+            let path = format!(
+                "{}/testfiles/000-TestPak-{}-{}_P.pak",
+                env!("CARGO_MANIFEST_DIR"),
+                mount_point,
+                compression
+            );
+            read_all(std::fs::read(path).unwrap())
+        };
+        let (file_version, compressed) = read("C");
+        let (_, uncompressed) = read("NoC");
+        assert_eq!(compressed.len(), 5);
+        assert_eq!(compressed, uncompressed);
+
+        // writing the records again gives the same contents
+        let mut output = Cursor::new(Vec::new());
+        let mut pak = PakFile::from_writer(file_version, &mut output);
+        for (name, data) in &compressed {
+            let record =
+                PakRecord::new(name.clone(), data.clone(), CompressionMethod::Zlib).unwrap();
+            pak.add_record(record).unwrap();
+        }
+        pak.write().unwrap();
+        drop(pak);
+        assert_eq!(read_all(output.into_inner()), (file_version, compressed));
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::Cursor;
+
+use unreal_pak::signature::{PakSignature, PakSigningKey, SIGNATURE_CHUNK_SIZE};
+
+/// 1024 bit test key, big endian hex
+const MODULUS: &str = "cb9cc1cca76a344736e1e367112a93bc6c89c15348392da905cf0e4973e297bd\
+4babb4baa2f6d42fa76aa9d01ea425b12b00dec3d9a272ee927502b45f0bf012b0308301cfc5da3f49fca2533c\
+7640e26fa0be3eb2076ffc8af441cdb32c6bcd749141938ffe4f253e0211c5736e085fe30a0e8ae9c18a4bb01c\
+f61ba7a32d39";
+const PUBLIC_EXPONENT: &str = "010001";
+const PRIVATE_EXPONENT: &str = "a84c07bc3b5d039d217610daae4bbd156fb9657c8b2407e3bf9367c1baf1\
+be6e8ecf7c7110266938826f1db192c2270d8bf80a2964086c6b59cf31cf1908eb036dd85d7c0e326704dd887dec\
+f51aedfce4a11ed24d6ad0a7d59a52a6b620f23843d06aba7d61489a09ec0fffb6062ded4f88119f3fc9d94499c8\
+3441fd36eb29";
+
+/// Little endian bytes of a big endian hex string
+fn le_bytes(hex: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|e| u8::from_str_radix(&hex[e..e + 2], 16).unwrap())
+        .collect();
+    bytes.reverse();
+    bytes
+}
+
+fn signing_key() -> PakSigningKey {
+    PakSigningKey::from_components(
+        &le_bytes(MODULUS),
+        &le_bytes(PUBLIC_EXPONENT),
+        &le_bytes(PRIVATE_EXPONENT),
+    )
+    .unwrap()
+}
+
+#[test]
+fn sign_and_verify() {
+    let key = signing_key();
+    let verifying_key = key.verifying_key();
+    let data: Vec<u8> = (0..200_000u32).map(|e| (e * 7 % 251) as u8).collect();
+
+    let signature = PakSignature::sign(&mut Cursor::new(&data), &key).unwrap();
+    assert_eq!(
+        signature.chunk_hashes.len(),
+        data.len().div_ceil(SIGNATURE_CHUNK_SIZE)
+    );
+    assert_eq!(signature.encrypted_hash.len(), 128);
+
+    let mut sig = Vec::new();
+    signature.write(&mut sig).unwrap();
+    let signature = PakSignature::read(&mut Cursor::new(&sig)).unwrap();
+    assert!(signature
+        .verify(&mut Cursor::new(&data), &verifying_key)
+        .unwrap()
+        .is_ok());
+
+    // changed and missing chunks are reported, the signature itself is still valid
+    let mut modified = data.clone();
+    modified[70_000] ^= 1;
+    modified.truncate(140_000);
+    let report = signature
+        .verify(&mut Cursor::new(&modified), &verifying_key)
+        .unwrap();
+    assert!(report.signature_valid);
+    assert_eq!(report.mismatched_chunks, [1, 2, 3]);
+
+    // chunk hashes that don't match the signed hash
+    let mut forged = signature.clone();
+    forged.chunk_hashes[0] ^= 1;
+    let report = forged
+        .verify(&mut Cursor::new(&data), &verifying_key)
+        .unwrap();
+    assert!(!report.signature_valid);
+}
+
+#[test]
+fn hostile_counts() {
+    let signature = PakSignature::sign(&mut Cursor::new(vec![1u8; 10]), &signing_key()).unwrap();
+    let mut sig = Vec::new();
+    signature.write(&mut sig).unwrap();
+
+    for count in [i32::MAX, -1] {
+        let mut hostile = sig.clone();
+        hostile[8..12].copy_from_slice(&count.to_le_bytes());
+        assert!(PakSignature::read(&mut Cursor::new(&hostile)).is_err());
+    }
+}
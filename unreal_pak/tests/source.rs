@@ -0,0 +1,74 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Cursor, Read, Seek, Write};
+
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const FILES: [(&str, &[u8]); 2] = [
+    ("Game/Content/a.uasset", b"first record"),
+    ("Game/Content/b.uexp", b"second record"),
+];
+
+fn add_records<R, W>(pak: &mut PakFile<'_, R, W>)
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    for (name, data) in FILES {
+        let record =
+            PakRecord::new(name.to_string(), data.to_vec(), CompressionMethod::Zlib).unwrap();
+        pak.add_record(record).unwrap();
+    }
+}
+
+fn check<R>(mut pak: PakFile<'_, R>)
+where
+    R: Read + Seek,
+{
+    pak.load_records().unwrap();
+    assert_eq!(pak.records.len(), FILES.len());
+    for (name, data) in FILES {
+        let record = pak.get_record(&name.to_string()).unwrap();
+        assert_eq!(record.data.as_deref(), Some(data));
+    }
+}
+
+fn write_in_memory() -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    add_records(&mut pak);
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+#[test]
+fn file_and_memory_sources_match() {
+    let path = std::env::temp_dir().join(format!("unreal_pak_source_{}.pak", std::process::id()));
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    let mut pak = PakFile::writer(PakVersion::PakFileVersionFnv64bugFix, &file);
+    add_records(&mut pak);
+    pak.write().unwrap();
+    drop(pak);
+    drop(file);
+
+    let data = write_in_memory();
+    assert_eq!(fs::read(&path).unwrap(), data);
+
+    let file = File::open(&path).unwrap();
+    check(PakFile::reader(&file));
+    drop(file);
+    fs::remove_file(&path).unwrap();
+
+    // owned and borrowed buffers, and readers wrapping other readers
+    check(PakFile::from_reader(Cursor::new(data.clone())));
+    check(PakFile::from_reader(Cursor::new(data.as_slice())));
+    check(PakFile::from_reader(BufReader::new(Cursor::new(data))));
+}
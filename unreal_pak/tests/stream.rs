@@ -0,0 +1,121 @@
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+use unreal_pak::encryption::AesKey;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const KEY: [u8; 32] = [0x3c; 32];
+const BLOCK_SIZE: usize = 0x1000;
+
+fn record_data() -> Vec<u8> {
+    (0..5 * BLOCK_SIZE + 123)
+        .map(|i| (i / 7 % 251) as u8)
+        .collect()
+}
+
+fn write(compression_method: CompressionMethod, encrypt_records: bool) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.block_size = BLOCK_SIZE as u32;
+    pak.encrypt_records = encrypt_records;
+    pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+    let record = PakRecord::new(
+        "Game/stream.bin".to_string(),
+        record_data(),
+        compression_method,
+    )
+    .unwrap();
+    pak.add_record(record).unwrap();
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn load(data: Vec<u8>) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+    pak.load_records().unwrap();
+    pak
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, pos: SeekFrom, len: usize) -> Vec<u8> {
+    reader.seek(pos).unwrap();
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn seek_and_read() {
+    let expected = record_data();
+    let len = expected.len();
+    let name = "Game/stream.bin".to_string();
+
+    for compression_method in [CompressionMethod::None, CompressionMethod::Zlib] {
+        for encrypt_records in [false, true] {
+            let mut pak = load(write(compression_method.clone(), encrypt_records));
+            let mut reader = pak.get_record_reader(&name).unwrap();
+            assert_eq!(reader.len(), len as u64);
+
+            let mut all = Vec::new();
+            reader.read_to_end(&mut all).unwrap();
+            assert_eq!(all, expected);
+
+            // within a block, across blocks and into the partial last block
+            for (start, size) in [
+                (0, 10),
+                (BLOCK_SIZE - 3, 6),
+                (2 * BLOCK_SIZE + 17, 2 * BLOCK_SIZE),
+                (len - 200, 200),
+            ] {
+                let read = read_at(&mut reader, SeekFrom::Start(start as u64), size);
+                assert_eq!(read, expected[start..start + size]);
+            }
+
+            let read = read_at(&mut reader, SeekFrom::End(-50), 50);
+            assert_eq!(read, expected[len - 50..]);
+
+            reader.seek(SeekFrom::Start(3 * BLOCK_SIZE as u64)).unwrap();
+            let read = read_at(&mut reader, SeekFrom::Current(-100), 100);
+            assert_eq!(read, expected[3 * BLOCK_SIZE - 100..3 * BLOCK_SIZE]);
+            assert_eq!(reader.stream_position().unwrap(), 3 * BLOCK_SIZE as u64);
+
+            // past the end reads nothing, before the start is an error
+            reader.seek(SeekFrom::End(10)).unwrap();
+            assert_eq!(reader.read(&mut [0u8; 16]).unwrap(), 0);
+            let err = reader
+                .seek(SeekFrom::Current(-(len as i64) - 20))
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
+
+#[test]
+fn read_from_separate_reader() {
+    let data = write(CompressionMethod::Zlib, true);
+    let pak = load(data.clone());
+    let name = "Game/stream.bin".to_string();
+
+    // two readers on their own sources can be used at the same time
+    let mut first_source = Cursor::new(data.clone());
+    let mut second_source = Cursor::new(data);
+    let mut first = pak
+        .get_record_reader_from(&name, &mut first_source)
+        .unwrap();
+    let mut second = pak
+        .get_record_reader_from(&name, &mut second_source)
+        .unwrap();
+
+    let expected = record_data();
+    let start = 4 * BLOCK_SIZE as u64;
+    assert_eq!(
+        read_at(&mut first, SeekFrom::Start(start), 64),
+        expected[start as usize..start as usize + 64]
+    );
+    assert_eq!(read_at(&mut second, SeekFrom::Start(0), 64), expected[..64]);
+    assert_eq!(
+        read_at(&mut first, SeekFrom::Current(0), 64),
+        expected[start as usize + 64..start as usize + 128]
+    );
+}
@@ -0,0 +1,146 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use unreal_pak::encryption::AesKey;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const KEY: [u8; 32] = [0x17; 32];
+
+fn data(seed: u8, len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|e| ((e * 7 + seed as usize) % 251) as u8)
+        .collect()
+}
+
+fn record(name: &str, data: Vec<u8>, compression_method: CompressionMethod) -> PakRecord {
+    PakRecord::new(name.to_string(), data, compression_method).unwrap()
+}
+
+/// Open a pak for updating and run `update` on it
+fn update(path: &Path, update: impl FnOnce(&mut PakFile)) {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    let mut pak = PakFile::updater(&file);
+    pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+    pak.load_records().unwrap();
+    update(&mut pak);
+}
+
+/// Check that a pak verifies and holds exactly the given records, `None` for delete records
+fn check(path: &Path, encrypted: bool, expected: &[(&str, Option<Vec<u8>>)]) {
+    let file = File::open(path).unwrap();
+    let mut pak = PakFile::reader(&file);
+    pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+    pak.load_records().unwrap();
+    let report = pak.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.issues);
+
+    assert_eq!(pak.records.len(), expected.len());
+    for (name, data) in expected {
+        let record = pak.get_record(&name.to_string()).unwrap();
+        match data {
+            Some(data) => {
+                assert_eq!(record.data.as_ref(), Some(data), "{name}");
+                assert_eq!(record.is_encrypted(), encrypted, "{name}");
+            }
+            None => assert!(record.is_deleted(), "{name}"),
+        }
+    }
+}
+
+#[test]
+fn update_delete_compact() {
+    let dir = std::env::temp_dir().join(format!("unreal_pak_update_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    for file_version in [
+        PakVersion::PakFileVersionCompressionEncryption,
+        PakVersion::PakFileVersionRelativeChunkOffsets,
+        PakVersion::PakFileVersionFnameBasedCompressionMethod,
+        PakVersion::PakFileVersionFnv64bugFix,
+    ] {
+        for encrypt in [false, true] {
+            let path: PathBuf = dir.join(format!("{:?}_{}.pak", file_version, encrypt));
+            {
+                let file = File::create(&path).unwrap();
+                let mut pak = PakFile::writer(file_version, &file);
+                pak.add_encryption_key([0u8; 16], AesKey::new(KEY));
+                pak.encrypt_records = encrypt;
+                pak.encrypt_index =
+                    encrypt && file_version >= PakVersion::PakFileVersionIndexEncryption;
+                pak.add_record(record("a", data(1, 200_000), CompressionMethod::Zlib))
+                    .unwrap();
+                pak.add_record(record("b", data(2, 100_000), CompressionMethod::None))
+                    .unwrap();
+                pak.add_record(record("c", data(3, 150_000), CompressionMethod::Zlib))
+                    .unwrap();
+                pak.write().unwrap();
+            }
+            let original_size = fs::metadata(&path).unwrap().len();
+
+            // replace a record, add one and then add another one with a second update
+            update(&path, |pak| {
+                pak.add_record(record("a", data(9, 10), CompressionMethod::Zlib))
+                    .unwrap();
+                pak.add_record(record("d", data(4, 70_000), CompressionMethod::Zlib))
+                    .unwrap();
+                pak.write().unwrap();
+                pak.add_record(record("e", data(5, 10), CompressionMethod::None))
+                    .unwrap();
+                pak.write().unwrap();
+            });
+            let updated_size = fs::metadata(&path).unwrap().len();
+            assert!(updated_size >= original_size);
+            check(
+                &path,
+                encrypt,
+                &[
+                    ("a", Some(data(9, 10))),
+                    ("b", Some(data(2, 100_000))),
+                    ("c", Some(data(3, 150_000))),
+                    ("d", Some(data(4, 70_000))),
+                    ("e", Some(data(5, 10))),
+                ],
+            );
+
+            // the index shrinks when a record is dropped and the file with it
+            update(&path, |pak| {
+                pak.records.remove("e");
+                pak.write().unwrap();
+            });
+            assert!(fs::metadata(&path).unwrap().len() < updated_size);
+            let updated_size = fs::metadata(&path).unwrap().len();
+
+            // drop one record, turn another into a delete record where the version can store it
+            let has_delete_records = file_version >= PakVersion::PakFileVersionDeleteRecords;
+            update(&path, |pak| {
+                pak.records.remove("b");
+                if has_delete_records {
+                    pak.add_record(PakRecord::new_deleted("c".to_string()))
+                        .unwrap();
+                }
+                pak.compact().unwrap();
+            });
+            assert!(fs::metadata(&path).unwrap().len() < updated_size);
+            let c = match has_delete_records {
+                true => None,
+                false => Some(data(3, 150_000)),
+            };
+            check(
+                &path,
+                encrypt,
+                &[
+                    ("a", Some(data(9, 10))),
+                    ("c", c),
+                    ("d", Some(data(4, 70_000))),
+                ],
+            );
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,214 @@
+use std::io::Cursor;
+
+use sha1::{Digest, Sha1};
+use unreal_pak::layout::PakLayout;
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::verify::{VerifyIssueKind, VerifyReport};
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+/// Version that stores every record header with its name in the index
+const VERSION: PakVersion = PakVersion::PakFileVersionFnameBasedCompressionMethod;
+
+fn write(files: &[(&str, Vec<u8>, CompressionMethod)]) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(VERSION, &mut output);
+    for (name, data, compression_method) in files {
+        let record =
+            PakRecord::new(name.to_string(), data.clone(), compression_method.clone()).unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    output.into_inner()
+}
+
+fn load(data: Vec<u8>) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut pak = PakFile::from_reader(Cursor::new(data));
+    pak.load_records().unwrap();
+    pak
+}
+
+fn verify(data: Vec<u8>) -> VerifyReport {
+    load(data).verify().unwrap()
+}
+
+/// Edit the index entry of a record following its name and update the index hash in the footer
+fn edit_index_entry(data: &mut [u8], name: &str, edit: impl FnOnce(&mut [u8])) {
+    let footer = data.len() - PakLayout::default().footer_size(VERSION) as usize;
+    let index_offset = u64::from_le_bytes(data[footer + 25..footer + 33].try_into().unwrap());
+    let index_size = u64::from_le_bytes(data[footer + 33..footer + 41].try_into().unwrap());
+    let index = index_offset as usize..(index_offset + index_size) as usize;
+
+    // the entry follows the null terminated name
+    let mut name = name.as_bytes().to_vec();
+    name.push(0);
+    let position = data[index.clone()]
+        .windows(name.len())
+        .position(|e| e == name)
+        .unwrap()
+        + index.start
+        + name.len();
+    edit(&mut data[position..index.end]);
+
+    let hash = Sha1::digest(&data[index]);
+    data[footer + 41..footer + 61].copy_from_slice(&hash);
+}
+
+/// Change the offset of a record in the index
+fn set_record_offset(data: &mut [u8], name: &str, offset: u64) {
+    edit_index_entry(data, name, |entry| {
+        entry[..8].copy_from_slice(&offset.to_le_bytes())
+    });
+}
+
+fn files() -> Vec<(&'static str, Vec<u8>, CompressionMethod)> {
+    let big: Vec<u8> = (0..300_000u32).map(|e| (e * 13 % 251) as u8).collect();
+    vec![
+        ("a/big", big, CompressionMethod::Zlib),
+        ("a/raw", vec![7; 20_000], CompressionMethod::None),
+        ("b/small", vec![8; 100], CompressionMethod::None),
+        ("c/empty", Vec::new(), CompressionMethod::Zstd),
+    ]
+}
+
+#[test]
+fn intact_pak() {
+    let report = verify(write(&files()));
+    assert!(report.is_ok(), "{:?}", report.issues);
+    assert_eq!(report.records_checked, 4);
+}
+
+#[test]
+fn corrupt_record_data() {
+    let mut data = write(&files());
+    let pak = load(data.clone());
+    let raw_offset = pak.records["a/raw"].offset as usize;
+    let big_offset = pak.records["a/big"].offset as usize;
+    data[raw_offset + 1000] ^= 0xff;
+    data[big_offset + 200] ^= 0xff;
+
+    let report = verify(data);
+    assert!(report
+        .issues
+        .iter()
+        .any(|e| e.record.as_deref() == Some("a/raw")
+            && matches!(e.kind, VerifyIssueKind::HashMismatch { .. })));
+    assert!(report
+        .issues
+        .iter()
+        .any(|e| e.record.as_deref() == Some("a/big")));
+    assert!(report
+        .issues
+        .iter()
+        .all(|e| matches!(e.record.as_deref(), Some("a/raw" | "a/big"))));
+}
+
+#[test]
+fn overlapping_records() {
+    let mut data = write(&files());
+    let pak = load(data.clone());
+    let raw_offset = pak.records["a/raw"].offset;
+
+    // both records start inside of a/raw but not inside of each other
+    set_record_offset(&mut data, "b/small", raw_offset + 100);
+    set_record_offset(&mut data, "c/empty", raw_offset + 10_000);
+
+    let report = verify(data);
+    for name in ["b/small", "c/empty"] {
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|e| e.record.as_deref() == Some(name)
+                    && e.kind
+                        == VerifyIssueKind::Overlap {
+                            record: String::from("a/raw"),
+                            offset: raw_offset,
+                        }),
+            "{name}: {:?}",
+            report.issues
+        );
+    }
+}
+
+#[test]
+fn record_out_of_bounds() {
+    let mut data = write(&files());
+    let len = data.len() as u64;
+    set_record_offset(&mut data, "a/raw", len - 10);
+
+    let report = verify(data);
+    assert!(report
+        .issues
+        .iter()
+        .any(|e| e.record.as_deref() == Some("a/raw")
+            && matches!(e.kind, VerifyIssueKind::RecordOutOfBounds { .. })));
+}
+
+#[test]
+fn offsets_that_overflow() {
+    let mut data = write(&files());
+    set_record_offset(&mut data, "a/raw", u64::MAX - 10);
+    set_record_offset(&mut data, "a/big", u64::MAX);
+
+    let report = verify(data);
+    for name in ["a/raw", "a/big"] {
+        assert!(report
+            .issues
+            .iter()
+            .any(|e| e.record.as_deref() == Some(name)
+                && matches!(e.kind, VerifyIssueKind::RecordOutOfBounds { .. })));
+    }
+}
+
+#[test]
+fn corrupt_primary_index() {
+    let mut data = write(&files());
+    let footer = data.len() - PakLayout::default().footer_size(VERSION) as usize;
+    let index_offset = u64::from_le_bytes(data[footer + 25..footer + 33].try_into().unwrap());
+    // first character of the mount point, the index still parses but its hash doesn't match
+    data[index_offset as usize + 4] ^= 0x01;
+
+    assert!(PakFile::from_reader(Cursor::new(data.clone()))
+        .load_records()
+        .is_err());
+
+    let report = PakFile::from_reader(Cursor::new(data)).verify().unwrap();
+    assert_eq!(report.records_checked, 4);
+    assert_eq!(report.issues.len(), 1);
+    assert_eq!(report.issues[0].offset, index_offset);
+    assert_eq!(
+        report.issues[0].kind,
+        VerifyIssueKind::IndexHashMismatch(String::from("primary index"))
+    );
+}
+
+#[test]
+fn blocks_out_of_order() {
+    // offset, sizes, compression method, hash and block count come before the blocks
+    const BLOCKS: usize = 8 * 3 + 4 + 20 + 4;
+    let data = write(&files());
+
+    // both still decompress to the right size, the data of the first block just ends up
+    // in the wrong place or twice
+    let mut swapped = data.clone();
+    edit_index_entry(&mut swapped, "a/big", |entry| {
+        let (first, second) = entry[BLOCKS..BLOCKS + 32].split_at_mut(16);
+        first.swap_with_slice(second);
+    });
+    let mut overlapping = data;
+    edit_index_entry(&mut overlapping, "a/big", |entry| {
+        entry.copy_within(BLOCKS..BLOCKS + 16, BLOCKS + 16);
+    });
+
+    for data in [swapped, overlapping] {
+        assert!(load(data.clone()).get_record(&"a/big".to_string()).is_err());
+
+        let report = verify(data);
+        assert!(report
+            .issues
+            .iter()
+            .any(|e| e.record.as_deref() == Some("a/big")
+                && matches!(e.kind, VerifyIssueKind::ReadFailed(_))));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::Cursor;
+
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::vfs::{pak_order, PakVfs};
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+fn pak(mount_point: &str, files: &[(&str, Option<&[u8]>)]) -> PakFile<'static, Cursor<Vec<u8>>> {
+    let mut output = Cursor::new(Vec::new());
+    let mut pak = PakFile::from_writer(PakVersion::PakFileVersionFnv64bugFix, &mut output);
+    pak.mount_point = mount_point.as_bytes().to_vec();
+    for (name, data) in files {
+        let record = match data {
+            Some(data) => {
+                PakRecord::new(name.to_string(), data.to_vec(), CompressionMethod::None).unwrap()
+            }
+            None => PakRecord::new_deleted(name.to_string()),
+        };
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+
+    let mut pak = PakFile::from_reader(Cursor::new(output.into_inner()));
+    pak.load_records().unwrap();
+    pak
+}
+
+#[test]
+fn patch_suffix() {
+    assert_eq!(pak_order("pakchunk0-Windows.pak", 4), 4);
+    assert_eq!(pak_order("pakchunk0-Windows_P.pak", 4), 104);
+    assert_eq!(pak_order("pakchunk0-Windows_p.pak", 4), 104);
+    assert_eq!(pak_order("pakchunk0_1_P.pak", 4), 204);
+    assert_eq!(pak_order("pakchunk0_3_p.pak", 4), 404);
+    assert_eq!(pak_order("Paks/~mods/999-Mod_P.PAK", 0), 100);
+}
+
+#[test]
+fn priority() {
+    let mut vfs = PakVfs::new();
+    let base = vfs.mount(
+        "pakchunk0-WindowsNoEditor.pak",
+        4,
+        pak(
+            "../../../",
+            &[
+                ("Game/Content/A.uasset", Some(b"base")),
+                ("Game/Content/B.uasset", Some(b"b")),
+                ("Game/Content/C.uasset", Some(b"c")),
+            ],
+        ),
+    );
+    let nine = vfs.mount(
+        "9-Mod_p.pak",
+        4,
+        pak(
+            "../../../Game/Content/",
+            &[("a.uasset", Some(b"nine")), ("C.uasset", None)],
+        ),
+    );
+    let ten = vfs.mount(
+        "10-Mod_P.pak",
+        4,
+        pak("../../../Game/", &[("Content/A.uasset", Some(b"ten"))]),
+    );
+
+    // numeric prefixes are compared as numbers, patch paks win over the base pak
+    assert_eq!(vfs.resolve("game/content/a.uasset").unwrap().pak, ten);
+    assert_eq!(
+        vfs.read("../../../Game\\Content\\A.uasset").unwrap(),
+        b"ten"
+    );
+    assert_eq!(
+        vfs.shadowed_by("Game/Content/A.uasset", nine).unwrap().pak,
+        ten
+    );
+    assert_eq!(vfs.resolve("Game/Content/B.uasset").unwrap().pak, base);
+
+    // delete records hide the file of the base pak
+    assert!(vfs.resolve("Game/Content/C.uasset").is_none());
+    assert!(vfs.read("Game/Content/C.uasset").is_err());
+}
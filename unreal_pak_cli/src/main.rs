@@ -1,11 +1,18 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::SystemTime;
 
 use clap::{Parser, Subcommand};
+use unreal_pak::chunk::PakChunker;
+use unreal_pak::error::UnrealPakError;
+use unreal_pak::order::PakOrder;
+use unreal_pak::pakversion::PakVersion;
 use unreal_pak::PakRecord;
 use walkdir::WalkDir;
 
@@ -22,7 +29,7 @@ struct Args {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Check an entire .pak file if it is valid.
+    /// Check an entire .pak file if it is valid, verifying hashes and record bounds.
     Check {
         /// The .pak file to check
         pakfile: String,
@@ -34,12 +41,45 @@ enum Commands {
         pakfile: String,
     },
 
+    /// List the records of a .pak file with their sizes, compression and hashes.
+    List {
+        /// The .pak file to list
+        pakfile: String,
+        /// Sort by name, offset, size, compressed-size or compression
+        #[clap(short, long, default_value = "name")]
+        sort: String,
+        /// Reverse the sort order
+        #[clap(short, long)]
+        reverse: bool,
+        /// Only list records matching a glob like `Game/Content/**/*.uasset`, can be given multiple times
+        #[clap(short, long, multiple_occurrences = true)]
+        filter: Vec<String>,
+        /// Only list records using this compression method, `None` for uncompressed records
+        #[clap(short, long)]
+        compression: Option<String>,
+        /// Print the records as a json array
+        #[clap(long, conflicts_with = "csv")]
+        json: bool,
+        /// Print the records as csv with a header row
+        #[clap(long)]
+        csv: bool,
+    },
+
     /// Extract a .pak file to a directory.
     Extract {
         /// The .pak file to extract
         pakfile: String,
         /// The directory to extract to, if not specified the .pak file name will be used
         outdir: Option<String>,
+        /// Only extract records matching a glob like `Game/Content/**/*.uasset`, can be given multiple times
+        #[clap(short, long, multiple_occurrences = true)]
+        include: Vec<String>,
+        /// Skip records matching a glob, can be given multiple times
+        #[clap(short, long, multiple_occurrences = true)]
+        exclude: Vec<String>,
+        /// Number of records to decompress in parallel, defaults to the number of cpus
+        #[clap(short, long)]
+        jobs: Option<usize>,
     },
 
     /// create a new .pak file from the files from a directory, optionally disabling compression.
@@ -51,6 +91,29 @@ enum Commands {
         /// Whether to compress the file
         #[clap(short, long)]
         no_compression: bool,
+        /// The compression method to use (Zlib, Gzip, Zstd or LZ4)
+        #[clap(short, long, default_value = "Zlib")]
+        compression: String,
+        /// The pak version to write
+        #[clap(long, default_value_t = 8)]
+        pak_version: i32,
+        /// The compression level, if not supplied the default level of the compression method is used
+        #[clap(long)]
+        compression_level: Option<i32>,
+        /// The size of the compression blocks in bytes
+        #[clap(long, default_value_t = 0x10000)]
+        block_size: u32,
+        /// An UnrealPak order file, records are ordered by name if not supplied
+        #[clap(long)]
+        order: Option<String>,
+        /// Split the files into pakchunk paks of at most this many bytes of file data,
+        /// the paks are written next to the .pak file with its name as suffix
+        #[clap(long)]
+        max_pak_size: Option<u64>,
+        /// Assign files matching a glob to a pakchunk, like `Game/Content/Maps/**=1`,
+        /// the first matching rule wins and other files go to pakchunk0
+        #[clap(long, multiple_occurrences = true)]
+        chunk: Vec<String>,
     },
 }
 
@@ -58,6 +121,8 @@ fn main() {
     let args = Args::parse();
 
     let start = SystemTime::now();
+    // machine readable output must not contain anything else
+    let quiet = matches!(args.commands, Commands::List { .. });
 
     match args.commands {
         Commands::CheckHeader { pakfile } => {
@@ -68,96 +133,217 @@ fn main() {
         Commands::Check { pakfile } => {
             let file = open_file(Path::new(&pakfile));
             let mut pak = unreal_pak::PakFile::reader(&file);
-            check_header(&mut pak);
 
-            // TODO: get rid of this clone
-            for (i, (record_name, _)) in pak.records.clone().iter().enumerate() {
-                println!("Record {}: {}", i, record_name);
+            // verify reads the index itself so index hash mismatches end up in the report
+            let report = match pak.verify() {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Error verifying pak file: {}", e);
+                    exit(1);
+                }
+            };
 
-                match pak.get_record(record_name) {
-                    Ok(_) => (),
+            println!("Checked {} records", report.records_checked);
+            for issue in &report.issues {
+                eprintln!("{}", issue);
+            }
+            if !report.is_ok() {
+                eprintln!("Found {} problems", report.issues.len());
+                exit(1);
+            }
+        }
+        Commands::List {
+            pakfile,
+            sort,
+            reverse,
+            filter,
+            compression,
+            json,
+            csv,
+        } => {
+            let file = open_file(Path::new(&pakfile));
+            let mut pak = unreal_pak::PakFile::reader(&file);
+            if let Err(e) = pak.load_records() {
+                eprintln!("Error reading header: {}", e);
+                exit(1);
+            }
+
+            let mut names: Vec<String> = match filter.is_empty() {
+                true => pak.records.keys().cloned().collect(),
+                false => {
+                    let index = pak.record_index();
+                    let mut names: Vec<String> = filter
+                        .iter()
+                        .flat_map(|e| index.glob(e))
+                        .map(|e| e.to_string())
+                        .collect();
+                    names.sort();
+                    names.dedup();
+                    names
+                }
+            };
+            if let Some(ref compression) = compression {
+                names.retain(|e| {
+                    let record = &pak.records[e];
+                    !record.is_deleted()
+                        && record
+                            .compression_method
+                            .name()
+                            .eq_ignore_ascii_case(compression)
+                });
+            }
+
+            let mut entries = Vec::with_capacity(names.len());
+            for name in names {
+                let hash = match pak.record_hash(&name) {
+                    Ok(hash) => hash,
                     Err(e) => {
-                        eprintln!("Error reading record {}: {}, Error: {}", i, record_name, e);
+                        eprintln!("Error reading record {}: {}", name, e);
                         exit(1);
                     }
+                };
+                entries.push(ListEntry::new(&pak.records[&name], hash));
+            }
+
+            match sort.as_str() {
+                "name" => {}
+                "offset" => entries.sort_by_key(|e| e.offset),
+                "size" => entries.sort_by_key(|e| e.size),
+                "compressed-size" => entries.sort_by_key(|e| e.compressed_size),
+                "compression" => entries.sort_by(|a, b| a.compression.cmp(&b.compression)),
+                _ => {
+                    eprintln!("Invalid sort key {}", sort);
+                    exit(1);
                 }
             }
+            if reverse {
+                entries.reverse();
+            }
+
+            if json {
+                print_json(&entries);
+            } else if csv {
+                print_csv(&entries);
+            } else {
+                print_table(&entries);
+            }
         }
-        Commands::Extract { pakfile, outdir } => {
+        Commands::Extract {
+            pakfile,
+            outdir,
+            include,
+            exclude,
+            jobs,
+        } => {
             let path = Path::new(&pakfile);
             let file = open_file(path);
             let mut pak = unreal_pak::PakFile::reader(&file);
             check_header(&mut pak);
 
-            // temp values required to extend lifetimes outside of match scope
-            let temp;
-            let temp2;
-            let output_folder: &Path = match outdir {
-                Some(ref outdir) => {
-                    temp = outdir.clone();
-                    Path::new(&temp)
-                }
-                None => {
-                    temp2 = path.parent().unwrap().join(path.file_stem().unwrap());
-                    &temp2
-                }
+            let output_folder = match outdir {
+                Some(ref outdir) => PathBuf::from(outdir),
+                None => path.parent().unwrap().join(path.file_stem().unwrap()),
             };
 
             println!("Extracting to {}", output_folder.display());
 
-            // TODO: get rid of this clone
-            for (i, (record_name, _)) in pak.records.clone().iter().enumerate() {
-                match pak.get_record(record_name) {
-                    Ok(record_data) => {
-                        let path = Path::new(output_folder).join(&record_name[..]);
-                        let dir_path = match path.parent() {
-                            Some(dir) => dir,
-                            None => {
-                                eprintln!("No parent directories found! {}: {}", i, record_name);
-                                exit(1);
+            let index = pak.record_index();
+            let mut names: Vec<&str> = match include.is_empty() {
+                true => pak.records.keys().map(|e| e.as_str()).collect(),
+                false => include.iter().flat_map(|e| index.glob(e)).collect(),
+            };
+            names.sort();
+            names.dedup();
+            let excluded: HashSet<&str> = exclude.iter().flat_map(|e| index.glob(e)).collect();
+            names.retain(|e| !excluded.contains(e));
+
+            let jobs = jobs
+                .or_else(|| std::thread::available_parallelism().ok().map(|e| e.get()))
+                .unwrap_or(1)
+                .max(1);
+
+            let next = AtomicUsize::new(0);
+            let extracted = AtomicUsize::new(0);
+            let deleted = AtomicUsize::new(0);
+            let failures = Mutex::new(Vec::new());
+            std::thread::scope(|scope| {
+                for _ in 0..jobs.min(names.len()) {
+                    scope.spawn(|| {
+                        // every thread reads through its own handle to the pak,
+                        // the records are left to the other threads if it can't be opened
+                        let mut reader = match File::open(path) {
+                            Ok(file) => BufReader::new(file),
+                            Err(e) => {
+                                failures
+                                    .lock()
+                                    .unwrap()
+                                    .push((path.display().to_string(), e.to_string()));
+                                return;
                             }
                         };
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(record_name) = names.get(i) else {
+                                break;
+                            };
+                            let record_name = record_name.to_string();
 
-                        // Create the parent directories, then files.
-                        match std::fs::create_dir_all(dir_path) {
-                            Ok(_) => {
-                                // Create the file
-                                let mut file = match File::create(&path) {
-                                    Ok(file) => file,
-                                    Err(_) => {
-                                        eprintln!("Error creating file! {}: {:?}", i, path);
-                                        exit(1);
-                                    }
-                                };
-                                // Write the file
-                                match file.write_all(record_data.data.as_ref().unwrap()) {
-                                    Ok(_) => {
-                                        println!("Record {}: {}", i, record_name);
-                                    }
-                                    Err(_) => {
-                                        eprintln!("Error writing to file! {}: {:?}", i, path);
-                                        exit(1);
-                                    }
-                                }
+                            if pak.records[&record_name].is_deleted() {
+                                println!("Record {}: {} (deleted)", i, record_name);
+                                deleted.fetch_add(1, Ordering::Relaxed);
+                                continue;
                             }
-                            Err(_) => {
-                                eprintln!("Error creating directories! {:?}", dir_path);
-                                exit(1);
+
+                            match extract_record(&pak, &record_name, &mut reader, &output_folder) {
+                                Ok(_) => {
+                                    println!("Record {}: {}", i, record_name);
+                                    extracted.fetch_add(1, Ordering::Relaxed);
+                                }
+                                Err(e) => failures.lock().unwrap().push((record_name, e)),
                             }
-                        };
-                    }
-                    Err(_) => {
-                        eprintln!("Error reading record {}: {}", i, record_name);
-                        exit(1);
-                    }
+                        }
+                    });
                 }
+            });
+
+            // records no thread got to because the pak couldn't be opened count as failed too
+            let failures = failures.into_inner().unwrap();
+            let extracted = extracted.into_inner();
+            let deleted = deleted.into_inner();
+            println!(
+                "Extracted {} of {} records, {} deleted, {} failed",
+                extracted,
+                names.len(),
+                deleted,
+                names.len() - extracted - deleted
+            );
+            if !failures.is_empty() {
+                for (record_name, e) in &failures {
+                    eprintln!("Failed: {}: {}", record_name, e);
+                }
+                exit(1);
             }
         }
         Commands::Create {
             indir,
             pakfile,
             no_compression,
+            compression,
+            pak_version,
+            compression_level,
+            block_size,
+            order,
+            max_pak_size,
+            chunk,
         } => {
+            let pak_version = match PakVersion::try_from(pak_version) {
+                Ok(pak_version) => pak_version,
+                Err(_) => {
+                    eprintln!("Invalid pak version {}", pak_version);
+                    exit(1);
+                }
+            };
+
             let pakfile = match pakfile {
                 Some(pakfile) => pakfile,
                 None => {
@@ -166,32 +352,38 @@ fn main() {
                     path.to_str().unwrap().to_string()
                 }
             };
-            println!("Creating {}", pakfile);
-
-            // clear file
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&pakfile)
-                .unwrap();
 
-            let file = OpenOptions::new().append(true).open(&pakfile).unwrap();
-
-            let mut pak = unreal_pak::PakFile::writer(
-                unreal_pak::pakversion::PakVersion::PakFileVersionFnameBasedCompressionMethod,
-                &file,
-            );
+            let order = order.map(|order| {
+                let order = std::fs::read_to_string(&order)
+                    .map_err(UnrealPakError::from)
+                    .and_then(|e| PakOrder::parse(&e));
+                match order {
+                    Ok(order) => order,
+                    Err(e) => {
+                        eprintln!("Error reading order file: {}", e);
+                        exit(1);
+                    }
+                }
+            });
 
             let compression_method = if no_compression {
                 unreal_pak::CompressionMethod::None
             } else {
-                unreal_pak::CompressionMethod::Zlib
+                unreal_pak::CompressionMethod::from_name(&compression)
             };
 
             println!("Using compression method: {:?}", compression_method);
 
-            // Get all files and write them to the .pak file
+            let settings = PakSettings {
+                pak_version,
+                compression_method,
+                compression_level,
+                block_size,
+                order,
+            };
+
+            // Get all files with their record names
+            let mut files = Vec::new();
             for entry in WalkDir::new(&indir) {
                 let entry = entry.unwrap();
                 if entry.file_type().is_file() {
@@ -202,38 +394,286 @@ fn main() {
                         record_name = record_name[1..].to_owned();
                     }
 
-                    println!("Adding record: {}", record_name);
+                    let size = entry.metadata().map(|e| e.len()).unwrap_or(0);
+                    files.push((record_name, file_path, size));
+                }
+            }
 
-                    let file_data = match std::fs::read(&file_path) {
-                        Ok(file_data) => file_data,
-                        Err(_) => {
-                            eprintln!("Error reading file! {}", file_path);
+            if max_pak_size.is_none() && chunk.is_empty() {
+                create_pak(&pakfile, &files, &settings);
+            } else {
+                let mut chunker = PakChunker::new();
+                chunker.max_size = max_pak_size;
+                for rule in &chunk {
+                    let parsed = rule
+                        .rsplit_once('=')
+                        .and_then(|(pattern, id)| Some((pattern, id.trim().parse().ok()?)));
+                    match parsed {
+                        Some((pattern, chunk_id)) => chunker.add_rule(pattern, chunk_id),
+                        None => {
+                            eprintln!("Invalid chunk rule {}, expected PATTERN=ID", rule);
                             exit(1);
                         }
-                    };
-
-                    let record = PakRecord::new(record_name.clone(), file_data, compression_method)
-                        .unwrap_or_else(|_| {
-                            panic!("Error creating record {}", record_name.clone())
-                        });
-                    pak.add_record(record)
-                        .unwrap_or_else(|_| panic!("Error adding record {}", record_name));
+                    }
+                }
+
+                let plan = chunker.assign(files.iter().map(|e| (e.0.as_str(), e.2)));
+                let files_by_name: HashMap<&str, _> =
+                    files.iter().map(|e| (e.0.as_str(), e)).collect();
+
+                let path = Path::new(&pakfile);
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                let stem = path.file_stem().unwrap().to_string_lossy();
+                for pak_chunk in &plan.chunks {
+                    let chunk_files: Vec<_> = pak_chunk
+                        .files
+                        .iter()
+                        .map(|e| files_by_name[e.as_str()].clone())
+                        .collect();
+                    let chunk_path = dir.join(format!("{}-{}.pak", pak_chunk.name(), stem));
+                    create_pak(chunk_path.to_str().unwrap(), &chunk_files, &settings);
+                }
+
+                let manifest_path = dir.join(format!("{}.manifest", stem));
+                println!("Writing manifest {}", manifest_path.display());
+                if let Err(e) = std::fs::write(&manifest_path, plan.manifest()) {
+                    eprintln!("Error writing manifest: {}", e);
+                    exit(1);
                 }
             }
+        }
+    }
+    if !quiet {
+        println!(
+            "upakcli took {:?} seconds...",
+            start.elapsed().unwrap().as_secs_f32()
+        )
+    }
+}
 
-            println!("Writing pak file to disk. For large files this may take a while.");
+/// A record as printed by `list`
+struct ListEntry {
+    name: String,
+    offset: u64,
+    size: u64,
+    compressed_size: u64,
+    compression: String,
+    blocks: usize,
+    sha1: String,
+    deleted: bool,
+}
 
-            pak.write().expect("Failed to write");
+impl ListEntry {
+    fn new(record: &PakRecord, hash: Vec<u8>) -> Self {
+        ListEntry {
+            name: record.file_name.clone(),
+            offset: record.offset,
+            size: record.decompressed_size,
+            compressed_size: record.compressed_size,
+            compression: record.compression_method.name().to_string(),
+            blocks: record.block_count(),
+            sha1: match record.is_deleted() {
+                true => String::new(),
+                false => hash.iter().map(|e| format!("{:02x}", e)).collect(),
+            },
+            deleted: record.is_deleted(),
         }
     }
+}
+
+fn print_table(entries: &[ListEntry]) {
     println!(
-        "upakcli took {:?} seconds...",
-        start.elapsed().unwrap().as_secs_f32()
-    )
+        "{:>12} {:>12} {:>12} {:<8} {:>6} {:<40} Name",
+        "Offset", "Size", "Compressed", "Method", "Blocks", "SHA1"
+    );
+    for entry in entries {
+        println!(
+            "{:>12} {:>12} {:>12} {:<8} {:>6} {:<40} {}{}",
+            entry.offset,
+            entry.size,
+            entry.compressed_size,
+            entry.compression,
+            entry.blocks,
+            entry.sha1,
+            entry.name,
+            match entry.deleted {
+                true => " (deleted)",
+                false => "",
+            }
+        );
+    }
+    println!(
+        "{} records, {} bytes, {} bytes compressed",
+        entries.len(),
+        entries.iter().map(|e| e.size).sum::<u64>(),
+        entries.iter().map(|e| e.compressed_size).sum::<u64>()
+    );
+}
+
+fn print_json(entries: &[ListEntry]) {
+    println!("[");
+    for (i, entry) in entries.iter().enumerate() {
+        println!(
+            "  {{\"name\": {}, \"offset\": {}, \"size\": {}, \"compressed_size\": {}, \"compression\": {}, \"blocks\": {}, \"sha1\": {}, \"deleted\": {}}}{}",
+            json_string(&entry.name),
+            entry.offset,
+            entry.size,
+            entry.compressed_size,
+            json_string(&entry.compression),
+            entry.blocks,
+            json_string(&entry.sha1),
+            entry.deleted,
+            match i + 1 < entries.len() {
+                true => ",",
+                false => "",
+            }
+        );
+    }
+    println!("]");
+}
+
+fn print_csv(entries: &[ListEntry]) {
+    println!("name,offset,size,compressed_size,compression,blocks,sha1,deleted");
+    for entry in entries {
+        println!(
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&entry.name),
+            entry.offset,
+            entry.size,
+            entry.compressed_size,
+            csv_field(&entry.compression),
+            entry.blocks,
+            entry.sha1,
+            entry.deleted
+        );
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Settings shared by every pak written by `create`
+struct PakSettings {
+    pak_version: PakVersion,
+    compression_method: unreal_pak::CompressionMethod,
+    compression_level: Option<i32>,
+    block_size: u32,
+    order: Option<PakOrder>,
+}
+
+/// Write a pak from files given by record name, path and size
+fn create_pak(pakfile: &str, files: &[(String, String, u64)], settings: &PakSettings) {
+    println!("Creating {}", pakfile);
+
+    // clear file
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(pakfile)
+        .unwrap();
+
+    let file = OpenOptions::new().append(true).open(pakfile).unwrap();
+
+    let mut pak = unreal_pak::PakFile::writer(settings.pak_version, &file);
+    pak.compression_level = settings.compression_level;
+    pak.block_size = settings.block_size;
+    pak.order = settings.order.clone();
+
+    for (record_name, file_path, _) in files {
+        println!("Adding record: {}", record_name);
+
+        let file_data = match std::fs::read(file_path) {
+            Ok(file_data) => file_data,
+            Err(_) => {
+                eprintln!("Error reading file! {}", file_path);
+                exit(1);
+            }
+        };
+
+        let record = PakRecord::new(
+            record_name.clone(),
+            file_data,
+            settings.compression_method.clone(),
+        )
+        .unwrap_or_else(|_| panic!("Error creating record {}", record_name.clone()));
+        pak.add_record(record)
+            .unwrap_or_else(|_| panic!("Error adding record {}", record_name));
+    }
+
+    println!("Writing pak file to disk. For large files this may take a while.");
+
+    pak.write().expect("Failed to write");
+}
+
+/// Extract a single record to its path inside of the output folder
+fn extract_record(
+    pak: &unreal_pak::PakFile,
+    record_name: &String,
+    reader: &mut BufReader<File>,
+    output_folder: &Path,
+) -> Result<(), String> {
+    let path = output_folder.join(
+        record_path(record_name)
+            .ok_or_else(|| String::from("record name escapes the output directory"))?,
+    );
+
+    let mut record_reader = pak
+        .get_record_reader_from(record_name, reader)
+        .map_err(|e| format!("error reading record: {}", e))?;
+
+    if let Some(dir_path) = path.parent() {
+        std::fs::create_dir_all(dir_path)
+            .map_err(|e| format!("error creating directories {:?}: {}", dir_path, e))?;
+    }
+    let mut file =
+        File::create(&path).map_err(|e| format!("error creating file {:?}: {}", path, e))?;
+    std::io::copy(&mut record_reader, &mut file)
+        .map_err(|e| format!("error writing to file {:?}: {}", path, e))?;
+    Ok(())
+}
+
+/// Relative path of a record, `None` if the record name could point outside of the output folder
+fn record_path(record_name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in record_name.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            // parent directories, drive letters and absolute paths
+            ".." => return None,
+            component if component.contains(':') => return None,
+            component => path.push(component),
+        }
+    }
+    if record_name.starts_with(['/', '\\']) || path.as_os_str().is_empty() {
+        return None;
+    }
+    Some(path)
 }
 
 fn open_file(path: &Path) -> File {
-    match OpenOptions::new().read(true).open(&path) {
+    match OpenOptions::new().read(true).open(path) {
         Ok(file) => file,
         Err(_) => {
             eprintln!("Could not find/open file");
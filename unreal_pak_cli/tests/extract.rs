@@ -0,0 +1,79 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::Command;
+
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+#[test]
+fn extract_rejects_paths_outside_of_the_output_folder() {
+    let dir = std::env::temp_dir().join(format!("unreal_pak_cli_extract_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let output_folder = dir.join("out");
+    fs::create_dir_all(&output_folder).unwrap();
+
+    let pak_path = dir.join("hostile.pak");
+    let file = File::create(&pak_path).unwrap();
+    // versions before the path hash index store record names as they are
+    let mut pak = PakFile::writer(PakVersion::PakFileVersionFnameBasedCompressionMethod, &file);
+    for name in [
+        "../escaped.txt",
+        "Game/../../escaped.txt",
+        "/absolute.txt",
+        "\\absolute.txt",
+        "C:/drive.txt",
+        "Game/ok.txt",
+    ] {
+        let record =
+            PakRecord::new(name.to_string(), b"data".to_vec(), CompressionMethod::None).unwrap();
+        pak.add_record(record).unwrap();
+    }
+    pak.write().unwrap();
+    drop(pak);
+    drop(file);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_unreal_pak_cli"))
+        .arg("extract")
+        .arg(&pak_path)
+        .arg(&output_folder)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1), "{stdout}{stderr}");
+    assert!(
+        stdout.contains("Extracted 1 of 6 records, 0 deleted, 5 failed"),
+        "{stdout}"
+    );
+    assert_eq!(
+        stderr.lines().filter(|e| e.starts_with("Failed: ")).count(),
+        5
+    );
+
+    assert_eq!(
+        fs::read(output_folder.join("Game/ok.txt")).unwrap(),
+        b"data"
+    );
+    let extracted: Vec<PathBuf> = walk(&dir);
+    assert_eq!(
+        extracted,
+        [dir.join("hostile.pak"), output_folder.join("Game/ok.txt")]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Every file below a directory, sorted
+fn walk(dir: &PathBuf) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        match path.is_dir() {
+            true => files.extend(walk(&path)),
+            false => files.push(path),
+        }
+    }
+    files.sort();
+    files
+}
@@ -0,0 +1,156 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+
+use unreal_pak::pakversion::PakVersion;
+use unreal_pak::{CompressionMethod, PakFile, PakRecord};
+
+const QUOTED: &str = "Game/say \"hi\", \\ok\\.txt";
+const CONTROL: &str = "Game/line\nbreak\t\u{1}.txt";
+const PLAIN: &str = "Game/Content/plain.uasset";
+const DELETED: &str = "Game/Content/removed.uasset";
+
+fn write_pak(path: &Path) {
+    let file = File::create(path).unwrap();
+    let mut pak = PakFile::writer(PakVersion::PakFileVersionFnameBasedCompressionMethod, &file);
+    pak.add_record(
+        PakRecord::new(
+            QUOTED.to_string(),
+            b"quoted".to_vec(),
+            CompressionMethod::None,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    pak.add_record(
+        PakRecord::new(
+            CONTROL.to_string(),
+            b"control".to_vec(),
+            CompressionMethod::None,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    pak.add_record(
+        PakRecord::new(
+            PLAIN.to_string(),
+            vec![b'p'; 0x1000],
+            CompressionMethod::Zlib,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    pak.add_record(PakRecord::new_deleted(DELETED.to_string()))
+        .unwrap();
+    pak.write().unwrap();
+}
+
+/// Offset, size, compressed size, compression, block count and sha1 of a record
+fn record_fields(path: &Path, name: &str) -> (u64, u64, u64, String, usize, String) {
+    let file = File::open(path).unwrap();
+    let mut pak = PakFile::reader(&file);
+    pak.load_records().unwrap();
+    let hash: String = pak
+        .record_hash(&name.to_string())
+        .unwrap()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect();
+    let record = &pak.records[name];
+    (
+        record.offset,
+        record.decompressed_size,
+        record.compressed_size,
+        record.compression_method.name().to_string(),
+        record.block_count(),
+        match record.is_deleted() {
+            true => String::new(),
+            false => hash,
+        },
+    )
+}
+
+fn list(path: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_unreal_pak_cli"))
+        .arg("list")
+        .arg(path)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn list_output_is_escaped() {
+    let dir = std::env::temp_dir().join(format!("unreal_pak_cli_list_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let pak_path = dir.join("list.pak");
+    write_pak(&pak_path);
+
+    let json_line = |name: &str, escaped: &str, deleted: bool| {
+        let (offset, size, compressed_size, compression, blocks, sha1) =
+            record_fields(&pak_path, name);
+        format!(
+            "  {{\"name\": \"{escaped}\", \"offset\": {offset}, \"size\": {size}, \"compressed_size\": {compressed_size}, \"compression\": \"{compression}\", \"blocks\": {blocks}, \"sha1\": \"{sha1}\", \"deleted\": {deleted}}}"
+        )
+    };
+    let csv_line = |name: &str, escaped: &str, deleted: bool| {
+        let (offset, size, compressed_size, compression, blocks, sha1) =
+            record_fields(&pak_path, name);
+        format!(
+            "{escaped},{offset},{size},{compressed_size},{compression},{blocks},{sha1},{deleted}"
+        )
+    };
+
+    // sorted by name
+    let expected = [
+        "[".to_string(),
+        json_line(PLAIN, "Game/Content/plain.uasset", false) + ",",
+        json_line(DELETED, "Game/Content/removed.uasset", true) + ",",
+        json_line(CONTROL, "Game/line\\nbreak\\t\\u0001.txt", false) + ",",
+        json_line(QUOTED, "Game/say \\\"hi\\\", \\\\ok\\\\.txt", false),
+        "]".to_string(),
+    ];
+    assert_eq!(
+        list(&pak_path, &["--json"]).lines().collect::<Vec<_>>(),
+        expected
+    );
+
+    // fields with quotes, commas or line breaks are quoted, quotes are doubled
+    let expected = [
+        "name,offset,size,compressed_size,compression,blocks,sha1,deleted".to_string(),
+        csv_line(PLAIN, "Game/Content/plain.uasset", false),
+        csv_line(DELETED, "Game/Content/removed.uasset", true),
+        csv_line(CONTROL, "\"Game/line\nbreak\t\u{1}.txt\"", false),
+        csv_line(QUOTED, "\"Game/say \"\"hi\"\", \\ok\\.txt\"", false),
+    ]
+    .map(|e| e + "\n")
+    .concat();
+    assert_eq!(list(&pak_path, &["--csv"]), expected);
+
+    // filters, compression and sorting
+    let names = |args: &[&str]| -> Vec<String> {
+        list(&pak_path, &[&["--csv"], args].concat())
+            .lines()
+            .skip(1)
+            .map(|e| e.split(',').next().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(
+        names(&["--filter", "game/content/*.uasset"]),
+        [PLAIN, DELETED]
+    );
+    assert_eq!(names(&["--compression", "zlib"]), [PLAIN]);
+    assert_eq!(
+        names(&["--sort", "size", "--reverse", "--filter", "Game/Content/**"]),
+        [PLAIN, DELETED]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}